@@ -1,6 +1,6 @@
 use anyhow::Result;
 use clap::Parser;
-use std::io::BufRead;
+use std::io::{self, Write};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -14,6 +14,7 @@ struct Args {
 }
 
 fn run(args: Args) -> Result<()> {
+    let mut stdout = io::stdout().lock();
     for filename in args.files {
         let file = clir::open(&filename);
         if let Err(err) = file {
@@ -21,29 +22,15 @@ fn run(args: Args) -> Result<()> {
             continue;
         }
 
-        let mut file = file.unwrap();
-        let mut line = String::new();
         let mut line_count = 1;
-        loop {
-            let bytes = file.read_line(&mut line).unwrap();
-            if bytes == 0 {
-                break;
+        for line in clir::lines(file.unwrap()) {
+            let line = line?;
+            let blank = clir::chomp(&line).is_empty();
+            if args.number || (args.number_nonblank && !blank) {
+                write!(stdout, "{line_count:>6}\t")?;
+                line_count += 1;
             }
-            let clean_line = line.trim_end();
-            if args.number {
-                println!("{line_count:>6}\t{clean_line}");
-                line_count = line_count + 1;
-            } else if args.number_nonblank {
-                if clean_line != "" {
-                    println!("{line_count:>6}\t{clean_line}");
-                    line_count = line_count + 1;
-                } else {
-                    println!("")
-                }
-            } else {
-                println!("{clean_line}");
-            }
-            line.clear();
+            stdout.write_all(&line)?;
         }
     }
     Ok(())
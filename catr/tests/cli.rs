@@ -11,6 +11,7 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const SPIDERS: &str = "tests/inputs/spiders.txt";
 const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+const BUSTLE_GZ: &str = "tests/inputs/the-bustle.txt.gz";
 
 // --------------------------------------------------
 #[test]
@@ -142,6 +143,31 @@ fn fox_b() -> Result<()> {
     run(&["-b", FOX], "tests/expected/fox.txt.b.out")
 }
 
+// --------------------------------------------------
+#[test]
+fn bustle_gz() -> Result<()> {
+    run(&[BUSTLE_GZ], "tests/expected/the-bustle.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_gz_n() -> Result<()> {
+    run(&["-n", BUSTLE_GZ], "tests/expected/the-bustle.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_gz_stdin() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/the-bustle.txt.stdin.out")?;
+    Command::cargo_bin(PRG)?
+        .write_stdin(fs::read(BUSTLE_GZ)?)
+        .arg("-")
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn spiders() -> Result<()> {
@@ -1,11 +1,70 @@
 use anyhow::Result;
+use serde::Serialize;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Write};
 
+/// Compressed stream formats recognised by [`open`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Identifies the format from the magic bytes at the start of a stream.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if is_bzip2(header) {
+            Some(Self::Bzip2)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::Xz)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether `header` starts a bzip2 stream: "BZh", a block size from 1 to 9,
+/// then the magic of the first block or, for an empty stream, of its end.
+fn is_bzip2(header: &[u8]) -> bool {
+    const BLOCK: [u8; 6] = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+    const END: [u8; 6] = [0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+    match header {
+        [b'B', b'Z', b'h', b'1'..=b'9', magic @ ..] => {
+            magic.starts_with(&BLOCK) || magic.starts_with(&END)
+        }
+        _ => false,
+    }
+}
+
+fn decode<R: BufRead + 'static>(mut reader: R) -> Result<Box<dyn BufRead>> {
+    let decoded: Box<dyn BufRead> = match Compression::detect(reader.fill_buf()?) {
+        Some(Compression::Gzip) => {
+            Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader)))
+        }
+        Some(Compression::Bzip2) => {
+            Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader)))
+        }
+        Some(Compression::Xz) => Box::new(BufReader::new(
+            liblzma::bufread::XzDecoder::new_multi_decoder(reader),
+        )),
+        Some(Compression::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        None => Box::new(reader),
+    };
+    Ok(decoded)
+}
+
+/// Opens `filename` (or stdin for `-`), transparently decompressing gzip,
+/// bzip2, xz and zstd input.
 pub fn open(filename: &str) -> Result<Box<dyn BufRead>> {
     match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+        "-" => decode(BufReader::new(io::stdin())),
+        _ => decode(BufReader::new(File::open(filename)?)),
     }
 }
 
@@ -16,3 +75,258 @@ pub fn out(filename: &str) -> Result<Box<dyn Write>> {
     };
     Ok(boxed)
 }
+
+/// Iterator over the raw lines of a reader, terminator included.
+pub struct Lines<R> {
+    reader: R,
+    terminator: u8,
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = Vec::new();
+        match self.reader.read_until(self.terminator, &mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(line)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Splits `reader` into byte lines without requiring valid UTF-8, so writing
+/// every line back out reproduces the input exactly.
+pub fn lines<R: BufRead>(reader: R) -> Lines<R> {
+    records(reader, b'\n')
+}
+
+/// Splits `reader` into records ending in `terminator`, such as the NUL
+/// that ends each path in `find -print0` output.
+pub fn records<R: BufRead>(reader: R, terminator: u8) -> Lines<R> {
+    Lines { reader, terminator }
+}
+
+/// Strips a trailing `\n` or `\r\n` from a line.
+pub fn chomp(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Writes per-file records and their total as JSON, either as one document,
+/// `{"files":[...],"total":{...}}`, or as NDJSON with a record per line and
+/// the total last as `{"total":{...}}`.
+#[derive(Debug)]
+pub struct Report {
+    ndjson: bool,
+    records: usize,
+}
+
+impl Report {
+    pub fn json() -> Self {
+        Self {
+            ndjson: false,
+            records: 0,
+        }
+    }
+
+    pub fn ndjson() -> Self {
+        Self {
+            ndjson: true,
+            records: 0,
+        }
+    }
+
+    pub fn record(&mut self, out: &mut impl Write, record: &impl Serialize) -> Result<()> {
+        if !self.ndjson {
+            out.write_all(if self.records == 0 {
+                b"{\"files\":["
+            } else {
+                b","
+            })?;
+        }
+        serde_json::to_writer(&mut *out, record)?;
+        if self.ndjson {
+            out.write_all(b"\n")?;
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Writes the total, ending the document.
+    pub fn finish(&self, out: &mut impl Write, total: &impl Serialize) -> Result<()> {
+        if self.ndjson {
+            out.write_all(b"{\"total\":")?;
+        } else {
+            if self.records == 0 {
+                out.write_all(b"{\"files\":[")?;
+            }
+            out.write_all(b"],\"total\":")?;
+        }
+        serde_json::to_writer(&mut *out, total)?;
+        out.write_all(b"}\n")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compression, Report, chomp, decode, lines, records};
+    use serde_json::json;
+    use std::io::{Cursor, Read, Write};
+
+    const TEXT: &[u8] = b"The quick brown fox\njumps over the lazy dog.\n";
+
+    fn decoded(compressed: Vec<u8>) -> Vec<u8> {
+        let mut out = Vec::new();
+        decode(Cursor::new(compressed))
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Compression::detect(b"\x1f\x8b\x08"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::detect(b"BZh91AY&SY\x00"),
+            Some(Compression::Bzip2)
+        );
+        assert_eq!(
+            Compression::detect(b"BZh9\x17\x72\x45\x38\x50\x90"),
+            Some(Compression::Bzip2)
+        );
+        // Text that merely starts like bzip2 is plain
+        assert_eq!(Compression::detect(b"BZh hello\n"), None);
+        assert_eq!(Compression::detect(b"BZh91AY"), None);
+        assert_eq!(Compression::detect(b"BZh01AY&SY"), None);
+        assert_eq!(
+            Compression::detect(b"\xfd7zXZ\x00\x00"),
+            Some(Compression::Xz)
+        );
+        assert_eq!(
+            Compression::detect(b"\x28\xb5\x2f\xfd"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::detect(b"plain text"), None);
+        assert_eq!(Compression::detect(b"\x1f"), None);
+        assert_eq!(Compression::detect(b""), None);
+    }
+
+    #[test]
+    fn test_decode_plain() {
+        assert_eq!(decoded(TEXT.to_vec()), TEXT);
+        assert_eq!(decoded(b"BZh hello\n".to_vec()), b"BZh hello\n");
+        assert_eq!(decoded(Vec::new()), b"");
+    }
+
+    #[test]
+    fn test_decode_gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(TEXT).unwrap();
+        let mut compressed = encoder.finish().unwrap();
+        // Concatenated members decode as one stream, like `zcat`
+        compressed.extend(compressed.clone());
+        assert_eq!(decoded(compressed), [TEXT, TEXT].concat());
+    }
+
+    #[test]
+    fn test_decode_bzip2() {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(TEXT).unwrap();
+        assert_eq!(decoded(encoder.finish().unwrap()), TEXT);
+
+        let encoder = bzip2::write::BzEncoder::new(Vec::new(), Default::default());
+        assert_eq!(decoded(encoder.finish().unwrap()), b"");
+    }
+
+    #[test]
+    fn test_decode_xz() {
+        let mut encoder = liblzma::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(TEXT).unwrap();
+        assert_eq!(decoded(encoder.finish().unwrap()), TEXT);
+    }
+
+    #[test]
+    fn test_decode_zstd() {
+        let compressed = zstd::encode_all(TEXT, 0).unwrap();
+        assert_eq!(decoded(compressed), TEXT);
+    }
+
+    #[test]
+    fn test_lines() {
+        let input: &[u8] = b"foo\n\xffbar\r\n\nbaz";
+        let read = lines(Cursor::new(input))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            read,
+            vec![
+                b"foo\n".to_vec(),
+                b"\xffbar\r\n".to_vec(),
+                b"\n".to_vec(),
+                b"baz".to_vec(),
+            ]
+        );
+        assert_eq!(read.concat(), input);
+    }
+
+    #[test]
+    fn test_records() {
+        let read = records(Cursor::new(b"a b\0\nc\0d"), b'\0')
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            read,
+            vec![b"a b\0".to_vec(), b"\nc\0".to_vec(), b"d".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_lines_empty() {
+        assert!(lines(Cursor::new(b"")).next().is_none());
+    }
+
+    #[test]
+    fn test_chomp() {
+        assert_eq!(chomp(b"foo\n"), b"foo");
+        assert_eq!(chomp(b"foo\r\n"), b"foo");
+        assert_eq!(chomp(b"foo"), b"foo");
+        assert_eq!(chomp(b"foo\n\n"), b"foo\n");
+        assert_eq!(chomp(b""), b"");
+    }
+
+    #[test]
+    fn test_report_json() {
+        let mut out = Vec::new();
+        let mut report = Report::json();
+        report.record(&mut out, &json!({"file": "a"})).unwrap();
+        report.record(&mut out, &json!({"file": "b"})).unwrap();
+        report.finish(&mut out, &json!({"count": 2})).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"files\":[{\"file\":\"a\"},{\"file\":\"b\"}],\"total\":{\"count\":2}}\n"
+        );
+
+        let mut out = Vec::new();
+        Report::json().finish(&mut out, &json!({})).unwrap();
+        assert_eq!(out, b"{\"files\":[],\"total\":{}}\n");
+    }
+
+    #[test]
+    fn test_report_ndjson() {
+        let mut out = Vec::new();
+        let mut report = Report::ndjson();
+        report.record(&mut out, &json!({"file": "a"})).unwrap();
+        report.record(&mut out, &json!({"file": "b"})).unwrap();
+        report.finish(&mut out, &json!({"count": 2})).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"file\":\"a\"}\n{\"file\":\"b\"}\n{\"total\":{\"count\":2}}\n"
+        );
+    }
+}
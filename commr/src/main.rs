@@ -1,6 +1,6 @@
-use anyhow::{Result, bail};
+use anyhow::{Result, anyhow, bail};
 use clap::Parser;
-use std::io::BufRead;
+use std::io::{self, BufRead, Write};
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -18,37 +18,108 @@ struct Args {
     insensitive: bool,
     #[arg(long("output-delimiter"), short('d'), default_value = "\t")]
     delimiter: String,
+    /// Fail at the first line out of sorted order
+    #[arg(long, conflicts_with = "nocheck_order")]
+    check_order: bool,
+    /// Don't check that the input is sorted
+    #[arg(long)]
+    nocheck_order: bool,
+    /// Compare lines exactly instead of ignoring surrounding whitespace
+    #[arg(long)]
+    no_trim: bool,
+    /// Print the number of lines in each column, then "total"
+    #[arg(long)]
+    total: bool,
+    /// Lines end in NUL instead of newline, in the input and the output
+    #[arg(short('z'), long)]
+    zero_terminated: bool,
 }
 
-fn print(args: &Args, col1: Option<String>, col2: Option<String>, col3: Option<String>) {
-    let mut v = Vec::new();
-    if let Some(value) = col1 {
-        if !args.hide_col1 {
-            v.push(value);
-        }
-    } else if let Some(value) = col2 {
-        if !args.hide_col2 {
-            if !args.hide_col1 {
-                let empty = String::from("");
-                v.push(empty);
-            }
-            v.push(value)
+impl Args {
+    fn terminator(&self) -> u8 {
+        if self.zero_terminated { b'\0' } else { b'\n' }
+    }
+}
+
+/// Prints `value` in `column` (0 for lines only in FILE1, 1 for lines only
+/// in FILE2 and 2 for lines in both), unless that column is hidden.
+fn print(args: &Args, out: &mut impl Write, column: usize, value: &[u8]) -> Result<()> {
+    let hidden = [args.hide_col1, args.hide_col2, args.hide_col3];
+    if hidden[column] {
+        return Ok(());
+    }
+    for _ in hidden[..column].iter().filter(|&&hide| !hide) {
+        out.write_all(args.delimiter.as_bytes())?;
+    }
+    out.write_all(value)?;
+    out.write_all(&[args.terminator()])?;
+    Ok(())
+}
+
+/// The key a line is compared by, byte by byte.
+fn normalize(line: &[u8], args: &Args) -> Vec<u8> {
+    let line = if args.zero_terminated {
+        line.strip_suffix(b"\0").unwrap_or(line)
+    } else {
+        clir::chomp(line)
+    };
+    let line = if args.no_trim {
+        line
+    } else {
+        line.trim_ascii()
+    };
+    if !args.insensitive {
+        return line.to_vec();
+    }
+    match std::str::from_utf8(line) {
+        Ok(line) => line.to_lowercase().into_bytes(),
+        Err(_) => line.to_ascii_lowercase(),
+    }
+}
+
+/// One of the files being compared, which notes the first line that sorts
+/// before the one above it.
+struct Input {
+    name: String,
+    lines: clir::Lines<Box<dyn BufRead>>,
+    number: usize,
+    last: Option<Vec<u8>>,
+    unsorted: bool,
+}
+
+impl Input {
+    fn open(name: &str, terminator: u8) -> Result<Self> {
+        let file = clir::open(name).map_err(|e| anyhow!("{name}: {e}"))?;
+        Ok(Self {
+            name: name.to_string(),
+            lines: clir::records(file, terminator),
+            number: 0,
+            last: None,
+            unsorted: false,
+        })
+    }
+
+    /// Reads the next line's key. An unsorted line is an error with
+    /// `--check-order` and a warning otherwise, unless `--nocheck-order`.
+    fn next(&mut self, args: &Args) -> Result<Option<Vec<u8>>> {
+        let Some(line) = self.lines.next() else {
+            return Ok(None);
+        };
+        let line = normalize(&line?, args);
+        self.number += 1;
+        if args.nocheck_order || self.unsorted {
+            return Ok(Some(line));
         }
-    } else if let Some(value) = col3 {
-        if !args.hide_col3 {
-            if !args.hide_col1 {
-                let empty = String::from("");
-                v.push(empty);
+        if self.last.as_ref().is_some_and(|last| line < *last) {
+            self.unsorted = true;
+            let message = format!("{}:{}: not in sorted order", self.name, self.number);
+            if args.check_order {
+                bail!(message);
             }
-            if !args.hide_col2 {
-                let empty = String::from("");
-                v.push(empty);
-            }
-            v.push(value)
+            eprintln!("{message}");
         }
-    }
-    if !v.is_empty() {
-        println!("{}", v.join(&args.delimiter));
+        self.last = Some(line.clone());
+        Ok(Some(line))
     }
 }
 
@@ -57,49 +128,39 @@ fn run(args: Args) -> Result<()> {
     if args.file1 == "-" && args.file2 == "-" {
         bail!(r#"Both input files cannot be STDIN ("-")"#)
     }
-    let mut lines1 = clir::open(&args.file1)?
-        .lines()
-        .map_while(Result::ok)
-        .map(|st| {
-            if args.insensitive {
-                return st.to_lowercase().trim().to_string();
-            }
-            return st.trim().to_string();
-        });
-    let mut lines2 = clir::open(&args.file2)?
-        .lines()
-        .map_while(Result::ok)
-        .map(|st| {
-            if args.insensitive {
-                return st.to_lowercase().trim().to_string();
-            }
-            return st.trim().to_string();
-        });
+    let mut input1 = Input::open(&args.file1, args.terminator())?;
+    let mut input2 = Input::open(&args.file2, args.terminator())?;
+    let mut stdout = io::stdout().lock();
+    let mut totals = [0; 3];
+    let mut emit = |column: usize, value: &[u8]| {
+        totals[column] += 1;
+        print(&args, &mut stdout, column, value)
+    };
 
-    let mut line1 = lines1.next();
-    let mut line2 = lines2.next();
+    let mut line1 = input1.next(&args)?;
+    let mut line2 = input2.next(&args)?;
     loop {
         match (&line1, &line2) {
             (Some(l1), Some(l2)) => {
                 if l1 == l2 {
-                    print(&args, None, None, Some(l1.to_string()));
-                    line1 = lines1.next();
-                    line2 = lines2.next();
+                    emit(2, l1)?;
+                    line1 = input1.next(&args)?;
+                    line2 = input2.next(&args)?;
                 } else if l1 < l2 {
-                    print(&args, Some(l1.to_string()), None, None);
-                    line1 = lines1.next();
+                    emit(0, l1)?;
+                    line1 = input1.next(&args)?;
                 } else {
-                    print(&args, None, Some(l2.to_string()), None);
-                    line2 = lines2.next();
+                    emit(1, l2)?;
+                    line2 = input2.next(&args)?;
                 }
             }
             (None, Some(l2)) => {
-                print(&args, None, Some(l2.to_string()), None);
-                line2 = lines2.next();
+                emit(1, l2)?;
+                line2 = input2.next(&args)?;
             }
             (Some(l1), None) => {
-                print(&args, Some(l1.to_string()), None, None);
-                line1 = lines1.next();
+                emit(0, l1)?;
+                line1 = input1.next(&args)?;
             }
             (None, None) => {
                 break;
@@ -107,6 +168,18 @@ fn run(args: Args) -> Result<()> {
         }
     }
 
+    if args.total {
+        let [only1, only2, both] = totals;
+        let delimiter = &args.delimiter;
+        write!(
+            stdout,
+            "{only1}{delimiter}{only2}{delimiter}{both}{delimiter}total"
+        )?;
+        stdout.write_all(&[args.terminator()])?;
+    }
+    if input1.unsorted || input2.unsorted {
+        bail!("input is not in sorted order");
+    }
     Ok(())
 }
 
@@ -10,6 +10,10 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FILE1: &str = "tests/inputs/file1.txt";
 const FILE2: &str = "tests/inputs/file2.txt";
 const BLANK: &str = "tests/inputs/blank.txt";
+const UNSORTED: &str = "tests/inputs/unsorted.txt";
+const SPACES: &str = "tests/inputs/spaces.txt";
+const PATHS1: &str = "tests/inputs/paths1.bin";
+const PATHS2: &str = "tests/inputs/paths2.bin";
 
 // --------------------------------------------------
 #[test]
@@ -338,3 +342,76 @@ fn file1_file2_123_delim() -> Result<()> {
 fn blank_file1() -> Result<()> {
     run(&[BLANK, FILE1], "tests/expected/blank_file1.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn unsorted_file1_warns() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/unsorted_file1.out")?;
+    Command::cargo_bin(PRG)?
+        .args([UNSORTED, FILE1])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(
+            "tests/inputs/unsorted.txt:3: not in sorted order\n\
+             input is not in sorted order\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_file1_check_order() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", FILE1, UNSORTED])
+        .assert()
+        .failure()
+        .stdout("\t\ta\nb\n\t\tc\n")
+        .stderr("tests/inputs/unsorted.txt:3: not in sorted order\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_file1_nocheck_order() -> Result<()> {
+    run(
+        &["--nocheck-order", UNSORTED, FILE1],
+        "tests/expected/unsorted_file1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn spaces_file1() -> Result<()> {
+    run(&[SPACES, FILE1], "tests/expected/spaces_file1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn spaces_file1_no_trim() -> Result<()> {
+    run(
+        &["--no-trim", SPACES, FILE1],
+        "tests/expected/spaces_file1.no_trim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_total() -> Result<()> {
+    run(
+        &["--total", FILE1, FILE2],
+        "tests/expected/file1_file2.total.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn paths1_paths2_zero_terminated_total() -> Result<()> {
+    let expected = fs::read("tests/expected/paths1_paths2.z.3.total.delim.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-z", "--total", "-3", "-d", ":", PATHS1, PATHS2])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
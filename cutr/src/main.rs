@@ -1,20 +1,80 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{ArgGroup, Parser, ValueEnum};
+use csv::{ByteRecord, ReaderBuilder, Terminator};
+use regex::bytes::Regex;
+use std::io::{self, BufWriter, Read, Write};
 use std::ops::Range;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Header {
+    Keep,
+    Drop,
+}
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(group(
+    ArgGroup::new("extract")
+        .required(true)
+        .args(["fields", "field_names", "bytes", "chars"])
+))]
 struct Args {
     #[arg(value_name = "FILE", default_value = "-")]
     files: Vec<String>,
-    #[arg(short, long, value_name = "BYTES", conflicts_with_all = &["chars", "fields"])]
+    #[arg(
+        short,
+        long,
+        value_name = "BYTES",
+        allow_hyphen_values = true,
+        conflicts_with_all = &["chars", "fields", "field_names"]
+    )]
     bytes: Option<String>,
-    #[arg(short, long, conflicts_with_all = &["bytes", "fields"])]
+    #[arg(
+        short,
+        long,
+        allow_hyphen_values = true,
+        conflicts_with_all = &["bytes", "fields", "field_names"]
+    )]
     chars: Option<String>,
-    #[arg(short, long, conflicts_with_all = &["bytes", "chars"])]
+    #[arg(
+        short,
+        long,
+        allow_hyphen_values = true,
+        conflicts_with_all = &["bytes", "chars", "field_names"]
+    )]
     fields: Option<String>,
+    /// Select the fields whose names in the first row are in NAMES
+    #[arg(
+        short('F'),
+        long,
+        value_name = "NAMES",
+        conflicts_with_all = &["bytes", "chars", "fields"]
+    )]
+    field_names: Option<String>,
+    /// Whether to print the first row of fields
+    #[arg(long, value_enum, value_name = "WHEN", default_value = "keep")]
+    header: Header,
     #[arg(short, long("delim"), value_name = "DELIMITER", default_value = "\t")]
     delimiter: Option<String>,
+    /// Split fields on matches of PATTERN, such as '\s+', instead of --delim
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        conflicts_with_all = &["delimiter", "bytes", "chars"]
+    )]
+    regex_delim: Option<String>,
+    /// With --fields, skip lines that have no delimiter
+    #[arg(short('s'), long)]
+    only_delimited: bool,
+    /// With --bytes, don't split multibyte characters; ignored otherwise
+    #[arg(short('n'))]
+    no_split: bool,
+    /// Select everything but the given bytes, characters or fields
+    #[arg(long)]
+    complement: bool,
+    /// Separate the selected ranges or fields with STRING
+    #[arg(long, value_name = "STRING")]
+    output_delimiter: Option<String>,
 }
 
 fn parse_number(s: &str, original_s: &str) -> Result<usize> {
@@ -43,8 +103,13 @@ enum Extract {
     Bytes(PosRanges),
     Chars(PosRanges),
     Fields(PosRanges),
+    Names(Vec<String>),
 }
 
+/// Parses a list of positions and ranges, where `N-` runs to the end of the
+/// line and `-M` from its start. Overlapping ranges are merged and the
+/// result sorted, as GNU cut does, so each position is selected once and in
+/// the order it appears in the input.
 fn parse_pos(s: String) -> Result<PosRanges> {
     let mut ranges = Vec::new();
 
@@ -54,9 +119,18 @@ fn parse_pos(s: String) -> Result<PosRanges> {
 
     for range in s.split(',') {
         match range.split_once('-') {
+            Some(("", "")) => return Err(anyhow::anyhow!("illegal list value: \"{}\"", range)),
+            Some((start, "")) => {
+                let start = parse_number(start, range)?;
+                ranges.push((start - 1)..usize::MAX);
+            }
+            Some(("", end)) => {
+                let end = parse_number(end, range)?;
+                ranges.push(0..end);
+            }
             Some((start, end)) => {
-                let start = parse_number(start, &range)?;
-                let end = parse_number(end, &range)?;
+                let start = parse_number(start, range)?;
+                let end = parse_number(end, range)?;
                 if start >= end {
                     return Err(anyhow::anyhow!(
                         "First number in range ({}) must be lower than second number ({})",
@@ -67,21 +141,197 @@ fn parse_pos(s: String) -> Result<PosRanges> {
                 ranges.push((start - 1)..end);
             }
             None => {
-                let single_index = parse_number(range, &range)?;
+                let single_index = parse_number(range, range)?;
                 ranges.push(single_index - 1..single_index);
             }
         }
     }
-    Ok(ranges)
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: PosRanges = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    Ok(merged)
+}
+
+/// The selected ranges of the `len` positions in a line, or with
+/// `complement` the ranges between them.
+fn select(ranges: &[Range<usize>], complement: bool, len: usize) -> PosRanges {
+    let clamped = ranges
+        .iter()
+        .map(|range| range.start.min(len)..range.end.min(len));
+    if !complement {
+        return clamped.filter(|range| !range.is_empty()).collect();
+    }
+    let mut gaps = Vec::new();
+    let mut start = 0;
+    for range in clamped {
+        gaps.push(start..range.start);
+        start = range.end;
+    }
+    gaps.push(start..len);
+    gaps.retain(|range| !range.is_empty());
+    gaps
+}
+
+/// Pushes the byte offset of each character in `line` to `offsets`. Each
+/// invalid UTF-8 sequence counts as one character, as it would once decoded.
+fn char_offsets(line: &[u8], offsets: &mut Vec<usize>) {
+    let mut start = 0;
+    for chunk in line.utf8_chunks() {
+        offsets.extend(chunk.valid().char_indices().map(|(i, _)| start + i));
+        start += chunk.valid().len();
+        if !chunk.invalid().is_empty() {
+            offsets.push(start);
+            start += chunk.invalid().len();
+        }
+    }
+}
+
+/// Shrinks byte `ranges` of `line` to whole characters as POSIX `-n` does:
+/// a range starting inside a character takes all of it, one ending inside
+/// a character leaves it out, and ranges left empty are dropped.
+fn whole_chars(line: &[u8], ranges: PosRanges) -> PosRanges {
+    let char_start = |mut i: usize| {
+        while i > 0 && i < line.len() && (line[i] & 0xc0) == 0x80 {
+            i -= 1;
+        }
+        i
+    };
+    ranges
+        .into_iter()
+        .map(|range| char_start(range.start)..char_start(range.end))
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+/// Parses a comma-separated list of field names.
+fn parse_names(s: String) -> Result<Vec<String>> {
+    let names: Vec<String> = s.split(',').map(String::from).collect();
+    if names.iter().any(|name| name.is_empty()) {
+        return Err(anyhow::anyhow!("illegal list value: \"{}\"", s));
+    }
+    Ok(names)
+}
+
+/// The positions of the fields named `names` in `header`, each the first
+/// field with that name, in the order they appear in the header.
+fn find_names(names: &[String], header: &[&[u8]]) -> Result<PosRanges> {
+    let mut positions = names
+        .iter()
+        .map(|name| {
+            header
+                .iter()
+                .position(|field| *field == name.as_bytes())
+                .ok_or_else(|| anyhow::anyhow!("unknown field name \"{}\"", name))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    positions.sort_unstable();
+    positions.dedup();
+    Ok(positions.into_iter().map(|i| i..i + 1).collect())
+}
+
+/// The fields to select from a file whose first row is `header`.
+fn field_ranges(extract: &Extract, header: &[&[u8]]) -> Result<PosRanges> {
+    match extract {
+        Extract::Names(names) => find_names(names, header),
+        Extract::Fields(ranges) => Ok(ranges.clone()),
+        _ => Ok(PosRanges::new()),
+    }
+}
+
+fn extract_fields<'a>(record: &'a ByteRecord, ranges: &[Range<usize>]) -> Vec<&'a [u8]> {
+    ranges
+        .iter()
+        .flat_map(|range| range.clone().filter_map(|i| record.get(i)))
+        .collect()
+}
+
+/// Reads `inner`, adding a line break at the end if it doesn't end in one,
+/// so that every record read from it ends in exactly one.
+struct Terminated<R> {
+    inner: R,
+    last: Option<u8>,
+}
+
+impl<R> Terminated<R> {
+    fn new(inner: R) -> Self {
+        Terminated { inner, last: None }
+    }
+}
+
+impl<R: Read> Read for Terminated<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read > 0 {
+            self.last = Some(buf[read - 1]);
+            return Ok(read);
+        }
+        match (self.last, buf.first_mut()) {
+            (Some(last), Some(first)) if last != b'\n' => {
+                *first = b'\n';
+                self.last = Some(b'\n');
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+/// Writes `field` as it appeared in the input, in quotes only if it must
+/// have been quoted there: when it holds `delimiter` or a line break, or
+/// starts with a quote. A carriage return ending the field is the end of a
+/// CRLF line, and is written as is after any closing quote.
+fn write_field(out: &mut impl Write, field: &[u8], delimiter: u8) -> io::Result<()> {
+    let (field, cr) = match field.strip_suffix(b"\r") {
+        Some(field) => (field, &b"\r"[..]),
+        None => (field, &b""[..]),
+    };
+    let quoted = field.starts_with(b"\"")
+        || field
+            .iter()
+            .any(|&byte| byte == delimiter || byte == b'\n' || byte == b'\r');
+    if !quoted {
+        out.write_all(field)?;
+        return out.write_all(cr);
+    }
+    out.write_all(b"\"")?;
+    for (i, part) in field.split(|&byte| byte == b'"').enumerate() {
+        if i > 0 {
+            out.write_all(b"\"\"")?;
+        }
+        out.write_all(part)?;
+    }
+    out.write_all(b"\"")?;
+    out.write_all(cr)
 }
 
 fn run(args: Args) -> Result<()> {
-    if args.delimiter.is_some() && args.delimiter.as_ref().unwrap().len() != 1 {
+    let delimiter = args.delimiter.as_deref().unwrap_or("\t");
+    if delimiter.len() != 1 {
         return Err(anyhow::anyhow!(
             "--delim \"{}\" must be a single byte",
-            args.delimiter.as_ref().unwrap()
+            delimiter
         ));
     }
+    let delimiter = delimiter.as_bytes()[0];
+    let regex_delim = match args.regex_delim {
+        Some(pattern) => {
+            let regex = Regex::new(&pattern)
+                .map_err(|_| anyhow::anyhow!("Invalid --regex-delim \"{}\"", pattern))?;
+            if regex.is_match(b"") {
+                return Err(anyhow::anyhow!(
+                    "--regex-delim \"{}\" must not match an empty string",
+                    pattern
+                ));
+            }
+            Some(regex)
+        }
+        None => None,
+    };
 
     let extract = if let Some(bytes) = args.bytes {
         Extract::Bytes(parse_pos(bytes)?)
@@ -89,52 +339,160 @@ fn run(args: Args) -> Result<()> {
         Extract::Chars(parse_pos(chars)?)
     } else if let Some(fields) = args.fields {
         Extract::Fields(parse_pos(fields)?)
+    } else if let Some(names) = args.field_names {
+        Extract::Names(parse_names(names)?)
     } else {
         return Err(anyhow::anyhow!("no extract type specified"));
     };
+    // Fields are rejoined with the input delimiter, or a tab when split on a
+    // pattern, and bytes and characters with nothing
+    let fields = matches!(extract, Extract::Fields(_) | Extract::Names(_));
+    let output_delimiter = match args.output_delimiter {
+        Some(output_delimiter) => output_delimiter,
+        None if fields && regex_delim.is_some() => "\t".to_string(),
+        None if fields => (delimiter as char).to_string(),
+        None => String::new(),
+    };
 
-    for file in args.files.into_iter() {
-        let mut file = clir::open(&file)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    for filename in args.files.into_iter() {
+        let file = match clir::open(&filename) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("{filename}: {err}");
+                continue;
+            }
+        };
         match &extract {
-            Extract::Chars(_ranges) => {
-                let mut line = String::new();
-                loop {
-                    let mut line = String::new();
-                    let bytes_read = file.read_line(&mut line)?;
-                    if bytes_read == 0 {
-                        break;
+            Extract::Chars(ranges) => {
+                // The byte offset of each character, then of the line's end
+                let mut offsets = Vec::new();
+                for line in clir::lines(file) {
+                    let line = line?;
+                    let line = clir::chomp(&line);
+                    offsets.clear();
+                    char_offsets(line, &mut offsets);
+                    let chars = offsets.len();
+                    offsets.push(line.len());
+                    let selected = select(ranges, args.complement, chars);
+                    for (i, range) in selected.into_iter().enumerate() {
+                        if i > 0 {
+                            out.write_all(output_delimiter.as_bytes())?;
+                        }
+                        out.write_all(&line[offsets[range.start]..offsets[range.end]])?;
+                    }
+                    out.write_all(b"\n")?;
+                }
+            }
+            Extract::Bytes(ranges) => {
+                for line in clir::lines(file) {
+                    let line = line?;
+                    let line = clir::chomp(&line);
+                    let mut ranges = select(ranges, args.complement, line.len());
+                    if args.no_split {
+                        ranges = whole_chars(line, ranges);
                     }
-                    for range in _ranges {
-                        for i in range.start..range.end {
-                            print!("{}", line.chars().nth(i).unwrap());
+                    for (i, range) in ranges.into_iter().enumerate() {
+                        if i > 0 {
+                            out.write_all(output_delimiter.as_bytes())?;
                         }
+                        out.write_all(&line[range])?;
                     }
-                    println!();
-                    line.clear();
+                    out.write_all(b"\n")?;
                 }
             }
-            Extract::Bytes(_ranges) => {
-                let mut line = String::new();
-                loop {
-                    let bytes_read = file.read_line(&mut line)?;
-                    if bytes_read == 0 {
-                        break;
+            Extract::Fields(_) | Extract::Names(_) => {
+                let mut ranges = PosRanges::new();
+                if let Some(regex) = &regex_delim {
+                    for (row, line) in clir::lines(file).enumerate() {
+                        let line = line?;
+                        let fields: Vec<_> = regex.split(clir::chomp(&line)).collect();
+                        if row == 0 {
+                            ranges = field_ranges(&extract, &fields)
+                                .map_err(|e| anyhow::anyhow!("{filename}: {e}"))?;
+                            if args.header == Header::Drop {
+                                continue;
+                            }
+                        }
+                        // A line without a delimiter prints whole, unless -s
+                        if fields.len() == 1 {
+                            if !args.only_delimited {
+                                out.write_all(fields[0])?;
+                                out.write_all(b"\n")?;
+                            }
+                            continue;
+                        }
+                        let selected = select(&ranges, args.complement, fields.len());
+                        let selected = selected.into_iter().flat_map(|range| &fields[range]);
+                        for (i, field) in selected.enumerate() {
+                            if i > 0 {
+                                out.write_all(output_delimiter.as_bytes())?;
+                            }
+                            out.write_all(field)?;
+                        }
+                        out.write_all(b"\n")?;
                     }
-                    let line_as_bytes = line.as_bytes();
-                    let mut vector_of_bytes = Vec::new();
-                    for range in _ranges {
-                        for i in range.start..range.end {
-                            vector_of_bytes.push(line_as_bytes[i]);
+                } else {
+                    let mut reader = ReaderBuilder::new()
+                        .delimiter(delimiter)
+                        .terminator(Terminator::Any(b'\n'))
+                        .has_headers(false)
+                        .flexible(true)
+                        .from_reader(Terminated::new(file));
+                    let mut record = ByteRecord::new();
+                    let mut row = 0;
+                    loop {
+                        let start = reader.position().line();
+                        let more = reader.read_byte_record(&mut record)?;
+                        // The reader skips blank lines, which still print as
+                        // blank lines: they are the lines it moved past besides
+                        // the record's own, one per line break in its fields and
+                        // one for its end
+                        let mut blanks = reader.position().line() - start;
+                        if more {
+                            let breaks = record.iter().flatten().filter(|&&b| b == b'\n').count();
+                            blanks -= breaks as u64 + 1;
+                        }
+                        if !args.only_delimited {
+                            for _ in 0..blanks {
+                                out.write_all(b"\n")?;
+                            }
+                        }
+                        if !more {
+                            break;
+                        }
+                        row += 1;
+                        if row == 1 {
+                            let header: Vec<_> = record.iter().collect();
+                            ranges = field_ranges(&extract, &header)
+                                .map_err(|e| anyhow::anyhow!("{filename}: {e}"))?;
+                            if args.header == Header::Drop {
+                                continue;
+                            }
                         }
-                        let string_of_bytes = String::from_utf8_lossy(&vector_of_bytes);
-                        println!("{}", string_of_bytes);
+                        // A line without a delimiter prints whole, unless -s
+                        if record.len() == 1 {
+                            if !args.only_delimited {
+                                write_field(&mut out, &record[0], delimiter)?;
+                                out.write_all(b"\n")?;
+                            }
+                            continue;
+                        }
+                        let selected = select(&ranges, args.complement, record.len());
+                        let fields = extract_fields(&record, &selected);
+                        for (i, field) in fields.into_iter().enumerate() {
+                            if i > 0 {
+                                out.write_all(output_delimiter.as_bytes())?;
+                            }
+                            write_field(&mut out, field, delimiter)?;
+                        }
+                        out.write_all(b"\n")?;
                     }
-                    line.clear();
                 }
             }
-            _ => unreachable!(),
         }
     }
+    out.flush()?;
     Ok(())
 }
 
@@ -146,8 +504,13 @@ fn main() {
 }
 // --------------------------------------------------
 #[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
 mod unit_tests {
-    use super::parse_pos;
+    use super::{
+        PosRanges, char_offsets, extract_fields, find_names, parse_pos, select, whole_chars,
+        write_field,
+    };
+    use csv::ByteRecord;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -210,9 +573,6 @@ mod unit_tests {
         let res = parse_pos("1,".to_string());
         assert!(res.is_err());
 
-        let res = parse_pos("1-".to_string());
-        assert!(res.is_err());
-
         let res = parse_pos("1-1-1".to_string());
         assert!(res.is_err());
 
@@ -261,10 +621,132 @@ mod unit_tests {
 
         let res = parse_pos("1,7,3-5".to_string());
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1, 6..7, 2..5]);
+        assert_eq!(res.unwrap(), vec![0..1, 2..5, 6..7]);
 
         let res = parse_pos("15,19-20".to_string());
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), vec![14..15, 18..20]);
+
+        // Open-ended ranges run to the end or from the start
+        let res = parse_pos("3-".to_string());
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![2..usize::MAX]);
+
+        let res = parse_pos("-5".to_string());
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..5]);
+
+        // Overlapping ranges are merged, adjacent ones kept apart
+        let res = parse_pos("1,1".to_string());
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..1]);
+
+        let res = parse_pos("4-,2-5,1,2".to_string());
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..1, 1..usize::MAX]);
+    }
+
+    #[test]
+    fn test_select() {
+        let ranges = [0..1, 2..4, 6..usize::MAX];
+        assert_eq!(select(&ranges, false, 10), vec![0..1, 2..4, 6..10]);
+        assert_eq!(select(&ranges, false, 3), vec![0..1, 2..3]);
+        assert_eq!(select(&ranges, false, 0), PosRanges::new());
+        assert_eq!(select(&ranges, true, 10), vec![1..2, 4..6]);
+        assert_eq!(select(&ranges, true, 3), vec![1..2]);
+        assert_eq!(select(&[1..2], true, 3), vec![0..1, 2..3]);
+        assert_eq!(select(&[], true, 3), vec![0..3]);
+    }
+
+    #[test]
+    fn test_char_offsets() {
+        let offsets = |line: &[u8]| {
+            let mut offsets = vec![];
+            char_offsets(line, &mut offsets);
+            offsets
+        };
+        assert!(offsets(b"").is_empty());
+        assert_eq!(offsets("a\u{e9}b".as_bytes()), vec![0, 1, 3]);
+        // Invalid sequences are one character each
+        assert_eq!(offsets(b"a\xc3b\xff\xfe"), vec![0, 1, 2, 3, 4]);
+        assert_eq!(offsets(b"\xe2\x82a"), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_whole_chars() {
+        // "aé€" is a, then two bytes for é and three for €
+        let line = "a\u{e9}\u{20ac}".as_bytes();
+        assert_eq!(whole_chars(line, vec![0..1]), vec![0..1]);
+        assert_eq!(whole_chars(line, vec![0..2]), vec![0..1]);
+        assert_eq!(whole_chars(line, vec![1..2]), PosRanges::new());
+        assert_eq!(whole_chars(line, vec![2..3]), vec![1..3]);
+        assert_eq!(whole_chars(line, vec![2..5]), vec![1..3]);
+        assert_eq!(whole_chars(line, vec![4..6]), vec![3..6]);
+        assert_eq!(whole_chars(line, vec![0..1, 4..5]), vec![0..1]);
+        assert_eq!(whole_chars(b"\x80\x80", vec![1..2]), vec![0..2]);
+    }
+
+    #[test]
+    fn test_find_names() {
+        let header: Vec<&[u8]> = vec![b"name", b"email", b"age", b"email"];
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // Fields come in header order, each once, from the first match
+        assert_eq!(
+            find_names(&names(&["email", "name"]), &header).unwrap(),
+            vec![0..1, 1..2]
+        );
+        assert_eq!(
+            find_names(&names(&["age", "age"]), &header).unwrap(),
+            vec![2..3]
+        );
+
+        // Names not in the header are an error
+        let res = find_names(&names(&["phone"]), &header);
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            r#"unknown field name "phone""#
+        );
+    }
+
+    #[test]
+    fn test_write_field() {
+        let written = |field: &[u8]| {
+            let mut out = vec![];
+            write_field(&mut out, field, b',').unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(written(b"plain; text"), "plain; text");
+        assert_eq!(written(b"say \"hi\""), "say \"hi\"");
+        assert_eq!(written(b"To Sir, with Love"), "\"To Sir, with Love\"");
+        assert_eq!(written(b"two\nlines"), "\"two\nlines\"");
+        assert_eq!(
+            written(b"\"quoted\", he said"),
+            "\"\"\"quoted\"\", he said\""
+        );
+    }
+
+    #[test]
+    fn test_extract_fields() {
+        let rec = ByteRecord::from(vec!["Captain", "Sham", "12345"]);
+        assert_eq!(extract_fields(&rec, &[0..1]), [b"Captain"]);
+        assert_eq!(extract_fields(&rec, &[1..2]), [b"Sham"]);
+        assert_eq!(
+            extract_fields(&rec, &[0..1, 2..3]),
+            [b"Captain".as_slice(), b"12345"]
+        );
+        assert_eq!(extract_fields(&rec, &[0..1, 3..4]), [b"Captain"]);
+        assert_eq!(
+            extract_fields(&rec, &[1..2, 0..1]),
+            [b"Sham".as_slice(), b"Captain"]
+        );
+        let rec = ByteRecord::from(vec![b"\xff".as_slice(), b"ok"]);
+        assert_eq!(extract_fields(&rec, &[0..1]), [b"\xff"]);
     }
 }
@@ -7,8 +7,10 @@ use std::fs;
 
 const PRG: &str = "cutr";
 const CSV: &str = "tests/inputs/movies1.csv";
+const QUOTED_CSV: &str = "tests/inputs/movies2.csv";
 const TSV: &str = "tests/inputs/movies1.tsv";
 const BOOKS: &str = "tests/inputs/books.tsv";
+const DF: &str = "tests/inputs/df.txt";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -58,7 +60,7 @@ fn dies_not_enough_args() -> Result<()> {
     dies(
         &[CSV],
         "the following required arguments were not provided:\n  \
-        <--fields <FIELDS>|--bytes <BYTES>|--chars <CHARS>>",
+        <--fields <FIELDS>|--field-names <NAMES>|--bytes <BYTES>|--chars <CHARS>>",
     )
 }
 
@@ -162,14 +164,11 @@ fn run(args: &[&str], expected_file: &str) -> Result<()> {
 }
 
 // --------------------------------------------------
-fn run_lossy(args: &[&str], expected_file: &str) -> Result<()> {
-    let contents = fs::read(expected_file)?;
-    let expected = String::from_utf8_lossy(&contents);
+fn run_bytes(args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read(expected_file)?;
     let output = Command::cargo_bin(PRG)?.args(args).output().expect("fail");
     assert!(output.status.success());
-
-    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
-    assert_eq!(stdout, expected);
+    assert_eq!(output.stdout, expected);
     Ok(())
 }
 
@@ -263,6 +262,15 @@ fn csv_f1_3() -> Result<()> {
     )
 }
 
+// --------------------------------------------------
+#[test]
+fn csv_quoted_f1_3() -> Result<()> {
+    run(
+        &[QUOTED_CSV, "-f", "1,3", "-d", ","],
+        "tests/expected/movies2.csv.f1,3.dcomma.out",
+    )
+}
+
 // --------------------------------------------------
 #[test]
 fn tsv_b1() -> Result<()> {
@@ -278,7 +286,7 @@ fn tsv_b2() -> Result<()> {
 // --------------------------------------------------
 #[test]
 fn tsv_b8() -> Result<()> {
-    run_lossy(&[TSV, "-b", "8"], "tests/expected/movies1.tsv.b8.out")
+    run_bytes(&[TSV, "-b", "8"], "tests/expected/movies1.tsv.b8.out")
 }
 
 // --------------------------------------------------
@@ -296,7 +304,7 @@ fn tsv_b2_3() -> Result<()> {
 // --------------------------------------------------
 #[test]
 fn tsv_b1_8() -> Result<()> {
-    run_lossy(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
+    run_bytes(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
 }
 
 // --------------------------------------------------
@@ -340,3 +348,288 @@ fn tsv_c1_8() -> Result<()> {
 fn repeated_value() -> Result<()> {
     run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_open() -> Result<()> {
+    run(&[TSV, "-f", "2-"], "tests/expected/movies1.tsv.f2-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f2_complement() -> Result<()> {
+    run(
+        &[CSV, "-f", "2", "-d", ",", "--complement"],
+        "tests/expected/movies1.csv.f2.complement.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f3_1_output_delimiter() -> Result<()> {
+    run(
+        &[CSV, "-f", "3,1", "-d", ",", "--output-delimiter", ";"],
+        "tests/expected/movies1.csv.f3,1.dcomma.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b2_3_complement() -> Result<()> {
+    run(
+        &[TSV, "-b", "2-3", "--complement"],
+        "tests/expected/movies1.tsv.b2-3.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c_open_ranges_output_delimiter() -> Result<()> {
+    run(
+        &[TSV, "-c", "-3,5-", "--output-delimiter", " | "],
+        "tests/expected/movies1.tsv.c-3,5-.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quoted_f1_3_long_output_delimiter() -> Result<()> {
+    run(
+        &[
+            QUOTED_CSV,
+            "-f",
+            "1,3",
+            "-d",
+            ",",
+            "--output-delimiter",
+            " | ",
+        ],
+        "tests/expected/movies2.csv.f1,3.dcomma.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn books_b1_2_no_split() -> Result<()> {
+    run(
+        &[BOOKS, "-b", "1-2", "-n"],
+        "tests/expected/books.b1-2.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn books_b2_4_no_split() -> Result<()> {
+    run(
+        &[BOOKS, "-n", "-b", "2,4"],
+        "tests/expected/books.b2,4.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn books_c_past_short_lines() -> Result<()> {
+    run(&[BOOKS, "-c", "20-40"], "tests/expected/books.c20-40.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn df_f1_5_regex_delim() -> Result<()> {
+    run(
+        &[DF, "--regex-delim", r"\s+", "-f", "1,5"],
+        "tests/expected/df.txt.f1,5.regex.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn df_f1_5_regex_delim_only_delimited() -> Result<()> {
+    run(
+        &[DF, "--regex-delim", r"\s+", "-f", "1,5", "-s"],
+        "tests/expected/df.txt.f1,5.regex.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn df_f2_4_regex_delim_complement_output_delimiter() -> Result<()> {
+    run(
+        &[
+            DF,
+            "--regex-delim",
+            " +",
+            "-f",
+            "2-4",
+            "--complement",
+            "--output-delimiter",
+            ",",
+        ],
+        "tests/expected/df.txt.f2-4.regex.complement.outdelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_undelimited() -> Result<()> {
+    // Without -s, a line without a delimiter prints whole
+    run(
+        &["tests/inputs/undelimited.tsv", "-f", "2"],
+        "tests/expected/undelimited.tsv.f2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_only_delimited() -> Result<()> {
+    run(
+        &[
+            "tests/inputs/undelimited.tsv",
+            "-f",
+            "2",
+            "--only-delimited",
+        ],
+        "tests/expected/undelimited.tsv.f2.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_regex_delim() -> Result<()> {
+    dies(
+        &[DF, "-f", "1", "--regex-delim", "("],
+        r#"Invalid --regex-delim "(""#,
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_empty_match_regex_delim() -> Result<()> {
+    dies(
+        &[DF, "-f", "1", "--regex-delim", r"\s*"],
+        r#"--regex-delim "\s*" must not match an empty string"#,
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_regex_delim_and_delim() -> Result<()> {
+    dies(
+        &[DF, "-f", "1", "--regex-delim", r"\s+", "-d", ","],
+        "cannot be used with",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_field_names() -> Result<()> {
+    run(
+        &[CSV, "-F", "year,title", "-d", ","],
+        "tests/expected/movies1.csv.Fyear,title.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_field_names_drop_header() -> Result<()> {
+    run(
+        &[
+            CSV,
+            "--field-names",
+            "year,title",
+            "-d",
+            ",",
+            "--header",
+            "drop",
+        ],
+        "tests/expected/movies1.csv.Fyear,title.dcomma.noheader.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_field_names_complement() -> Result<()> {
+    run(
+        &[CSV, "-F", "title", "--complement", "-d", ","],
+        "tests/expected/movies1.csv.Ftitle.complement.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_open_drop_header() -> Result<()> {
+    run(
+        &[TSV, "-f", "2-", "--header", "drop"],
+        "tests/expected/movies1.tsv.f2-.noheader.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn df_field_names_regex_delim() -> Result<()> {
+    run(
+        &[DF, "--regex-delim", r"\s+", "-F", "Used,Mounted", "-s"],
+        "tests/expected/df.txt.FUsed,Mounted.regex.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unknown_field_name() -> Result<()> {
+    dies(
+        &[CSV, "-F", "title,rating", "-d", ","],
+        &format!(r#"{CSV}: unknown field name "rating""#),
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_empty_field_name() -> Result<()> {
+    dies(
+        &[CSV, "-F", "title,", "-d", ","],
+        r#"illegal list value: "title,""#,
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_field_names_and_fields() -> Result<()> {
+    dies(&[CSV, "-F", "title", "-f", "1"], "cannot be used with")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_blank_line() -> Result<()> {
+    run(
+        &["tests/inputs/blank.tsv", "-f", "2"],
+        "tests/expected/blank.tsv.f2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_blank_line_only_delimited() -> Result<()> {
+    run(
+        &["tests/inputs/blank.tsv", "-f", "2", "-s"],
+        "tests/expected/blank.tsv.f2.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f2_3_multiline() -> Result<()> {
+    // A quoted field may span lines, and the file need not end in a newline
+    run(
+        &["tests/inputs/multiline.csv", "-f", "2-3", "-d", ","],
+        "tests/expected/multiline.csv.f2-3.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_c3_4() -> Result<()> {
+    // Invalid UTF-8 counts as one character per byte and is copied as is
+    run_bytes(
+        &["tests/inputs/latin1.txt", "-c", "3-4"],
+        "tests/expected/latin1.txt.c3-4.out",
+    )
+}
@@ -1,8 +1,30 @@
+use anyhow::{Result, anyhow, bail};
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use clir::Report;
+use globset::{Glob, GlobBuilder, GlobMatcher};
 use regex::Regex;
-use clap::Parser;
-use anyhow::Result;
-use walkdir::WalkDir;
-use std::fs::File;
+use serde::Serialize;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::fs::FileType;
+use std::io::{self, BufWriter, Write};
+use std::iter::Peekable;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::SystemTime;
+use users::{get_group_by_gid, get_user_by_name, get_user_by_uid};
+use walkdir::{DirEntry, WalkDir};
+
+/// Batched `--exec` command lines are kept well under the system's limit on
+/// argument length, counted as `exec` does: each argument's bytes, its NUL
+/// and its pointer.
+const BATCH_BYTES: usize = 128 * 1024;
+
+fn arg_bytes(arg: &OsStr) -> usize {
+    arg.len() + 1 + size_of::<usize>()
+}
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -14,54 +36,1155 @@ struct Args {
         value_name = "NAME",
         short,
         long("name"),
+        value_parser = parse_glob,
+        action = clap::ArgAction::Append,
+        help = "Glob to match file names",
+        num_args = 0..,
+    )]
+    names: Vec<GlobMatcher>,
+
+    #[arg(
+        value_name = "NAME",
+        long("iname"),
+        value_parser = parse_iglob,
+        action = clap::ArgAction::Append,
+        help = "Glob to match file names, ignoring case",
+        num_args = 0..,
+    )]
+    inames: Vec<GlobMatcher>,
+
+    #[arg(
+        value_name = "REGEX",
+        long("regex"),
         value_parser = Regex::new,
         action = clap::ArgAction::Append,
         help = "Regex to match file names",
         num_args = 0..,
     )]
-    names: Vec<Regex>,
+    regexes: Vec<Regex>,
+
+    #[arg(
+        value_name = "PATH",
+        long("path"),
+        value_parser = parse_glob,
+        action = clap::ArgAction::Append,
+        help = "Glob to match the whole path, where '*' also matches '/'",
+    )]
+    path_globs: Vec<GlobMatcher>,
 
     #[arg(
         short('t'),
         long("type"),
         value_name = "TYPE",
-        // value_parser = |s: &str| s.to_lowercase(),
+        value_enum,
         action = clap::ArgAction::Append,
         help = "Entry type to match",
         num_args = 0..,
     )]
-    entry_types: Vec<String>,
+    entry_types: Vec<EntryType>,
+
+    #[arg(
+        long,
+        value_name = "[+-]N[cwbkMG]",
+        value_parser = parse_size,
+        allow_hyphen_values = true,
+        action = ArgAction::Append,
+        help = "Size in units rounded up: more than (+), less than (-) or exactly N",
+    )]
+    size: Vec<Size>,
+
+    #[arg(
+        long,
+        value_name = "[+-]DAYS",
+        value_parser = parse_days,
+        allow_hyphen_values = true,
+        action = ArgAction::Append,
+        help = "Last modified more than (+), less than (-) or exactly DAYS days ago",
+    )]
+    mtime: Vec<Cmp>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_parser = parse_newer,
+        action = ArgAction::Append,
+        help = "Modified more recently than FILE",
+    )]
+    newer: Vec<SystemTime>,
+
+    #[arg(
+        long,
+        action = ArgAction::Append,
+        num_args = 0,
+        default_missing_value = "true",
+        help = "Empty file or directory",
+    )]
+    empty: Vec<bool>,
+
+    #[arg(
+        long,
+        value_name = "[-/]MODE",
+        value_parser = parse_perm,
+        allow_hyphen_values = true,
+        action = ArgAction::Append,
+        help = "Octal permissions: exactly MODE, all of (-) or any of (/) its bits",
+    )]
+    perm: Vec<Perm>,
+
+    #[arg(
+        long,
+        value_name = "USER",
+        value_parser = parse_user,
+        action = ArgAction::Append,
+        help = "Owned by USER, a name or numeric ID",
+    )]
+    user: Vec<u32>,
+
+    #[arg(long, value_name = "N", help = "Skip entries less than N levels deep")]
+    mindepth: Option<usize>,
+
+    #[arg(long, value_name = "N", help = "Descend at most N levels")]
+    maxdepth: Option<usize>,
+
+    #[arg(long, help = "Process a directory's contents before the directory")]
+    depth: bool,
+
+    #[arg(
+        long,
+        action = ArgAction::Append,
+        num_args = 0,
+        default_missing_value = "true",
+        help = "Print the path followed by a newline (the default action)",
+    )]
+    print: Vec<bool>,
+
+    #[arg(
+        long,
+        action = ArgAction::Append,
+        num_args = 0,
+        default_missing_value = "true",
+        help = "Print the path followed by a NUL byte",
+    )]
+    print0: Vec<bool>,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_parser = parse_format,
+        action = ArgAction::Append,
+        help = "Print FORMAT, expanding \\n, \\t, \\0 and %p %P %f %h %d %s %m %u %g %U %G %y %l %T@",
+    )]
+    printf: Vec<Format>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["print", "print0", "printf", "ndjson"],
+        help = "Report matching entries as one JSON document instead of printing them",
+    )]
+    json: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["print", "print0", "printf"],
+        help = "Report matching entries as a JSON object per line instead of printing them",
+    )]
+    ndjson: bool,
+
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        action = ArgAction::Append,
+        num_args = 1..,
+        value_terminator = ";",
+        allow_hyphen_values = true,
+        help = "Run COMMAND with {} as the path, ending with ';', or on batches of paths when ending with '{} +'",
+    )]
+    exec: Vec<String>,
+
+    // --exec ending in "{} +", rewritten by `main`
+    #[arg(
+        long,
+        hide = true,
+        action = ArgAction::Append,
+        num_args = 1..,
+        value_terminator = ";",
+        allow_hyphen_values = true
+    )]
+    exec_batch: Vec<String>,
+
+    #[arg(
+        long,
+        action = ArgAction::Append,
+        num_args = 0,
+        default_missing_value = "true",
+        help = "Delete the entry; implies --depth",
+    )]
+    delete: Vec<bool>,
+
+    #[arg(
+        long,
+        action = ArgAction::Append,
+        num_args = 0,
+        default_missing_value = "true",
+        help = "Match both tests (implied between adjacent tests)",
+    )]
+    and: Vec<bool>,
+
+    #[arg(
+        long,
+        action = ArgAction::Append,
+        num_args = 0,
+        default_missing_value = "true",
+        help = "Match either test",
+    )]
+    or: Vec<bool>,
+
+    #[arg(
+        long,
+        action = ArgAction::Append,
+        num_args = 0,
+        default_missing_value = "true",
+        help = "Negate the following test (also \"!\")",
+    )]
+    not: Vec<bool>,
+
+    // "(" and ")" on the command line, rewritten by `main`
+    #[arg(
+        long("("),
+        hide = true,
+        action = ArgAction::Append,
+        num_args = 0,
+        default_missing_value = "true"
+    )]
+    open: Vec<bool>,
+
+    #[arg(
+        long(")"),
+        hide = true,
+        action = ArgAction::Append,
+        num_args = 0,
+        default_missing_value = "true"
+    )]
+    close: Vec<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum EntryType {
+    #[value(name = "f")]
+    File,
+    #[value(name = "d")]
+    Dir,
+    #[value(name = "l")]
+    Link,
+}
+
+/// A numeric test argument: `+N` means more than N, `-N` less than N and `N`
+/// exactly N.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cmp {
+    Greater(u64),
+    Less(u64),
+    Equal(u64),
+}
+
+impl Cmp {
+    /// Parses a leading comparison and number, returning any suffix after it.
+    fn parse(s: &str) -> Result<(Self, &str)> {
+        let (make, rest): (fn(u64) -> Self, _) = match s.as_bytes().first() {
+            Some(b'+') => (Self::Greater, &s[1..]),
+            Some(b'-') => (Self::Less, &s[1..]),
+            _ => (Self::Equal, s),
+        };
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number = rest[..end]
+            .parse()
+            .map_err(|_| anyhow!("expected a number"))?;
+        Ok((make(number), &rest[end..]))
+    }
+
+    fn matches(self, value: u64) -> bool {
+        match self {
+            Self::Greater(n) => value > n,
+            Self::Less(n) => value < n,
+            Self::Equal(n) => value == n,
+        }
+    }
+}
+
+/// A `--size` argument, compared against the file size in `unit`-byte
+/// blocks, rounded up as in find.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Size {
+    cmp: Cmp,
+    unit: u64,
 }
 
-fn run(args: Args) -> Result<()> {
-    for path in args.paths {
-        for entry in WalkDir::new(path) {
-            let entry = entry?;
-            let name = entry.file_name().to_str().unwrap();
-            let matches_name = args.names.iter().any(|re| re.is_match(name));
-            // let matches_type = args.entry_types.iter().any(|t| t == entry.file_type());
-            let mut matches_type = true;
-            if !args.entry_types.is_empty() {
-                matches_type = matches_type && (entry.file_type().is_dir() && args.entry_types.contains("d"))
-                || (entry.file_type().is_file() && args.entry_types.contains("f"))
-                || (entry.file_type().is_symlink() && args.entry_types.contains("l"));
-                println!("{}", matches_type);
+/// Compiles a shell glob as find's `-name` and `-path` do: `*` and `?`
+/// match any character, including `/` and a leading `.`.
+fn parse_glob(s: &str) -> Result<GlobMatcher> {
+    Ok(Glob::new(s)?.compile_matcher())
+}
+
+fn parse_iglob(s: &str) -> Result<GlobMatcher> {
+    Ok(GlobBuilder::new(s)
+        .case_insensitive(true)
+        .build()?
+        .compile_matcher())
+}
+
+fn parse_size(s: &str) -> Result<Size> {
+    let (cmp, suffix) = Cmp::parse(s)?;
+    let unit = match suffix {
+        "c" => 1,
+        "w" => 2,
+        "" | "b" => 512,
+        "k" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => bail!("unknown unit \"{suffix}\""),
+    };
+    Ok(Size { cmp, unit })
+}
+
+fn parse_days(s: &str) -> Result<Cmp> {
+    match Cmp::parse(s)? {
+        (cmp, "") => Ok(cmp),
+        (_, suffix) => bail!("unexpected \"{suffix}\""),
+    }
+}
+
+fn parse_newer(s: &str) -> Result<SystemTime> {
+    Ok(fs::metadata(s)?.modified()?)
+}
+
+/// A `--perm` argument: `MODE` matches exactly, `-MODE` when all its bits
+/// are set and `/MODE` when any of them are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Perm {
+    Exact(u32),
+    All(u32),
+    Any(u32),
+}
+
+fn parse_perm(s: &str) -> Result<Perm> {
+    let (make, mode): (fn(u32) -> Perm, _) = if let Some(mode) = s.strip_prefix('-') {
+        (Perm::All, mode)
+    } else if let Some(mode) = s.strip_prefix('/') {
+        (Perm::Any, mode)
+    } else {
+        (Perm::Exact, s)
+    };
+    match u32::from_str_radix(mode, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(make(mode)),
+        _ => bail!("expected an octal mode"),
+    }
+}
+
+fn parse_user(s: &str) -> Result<u32> {
+    get_user_by_name(s)
+        .map(|user| user.uid())
+        .or_else(|| s.parse().ok())
+        .ok_or_else(|| anyhow!("no such user"))
+}
+
+/// A piece of a `--printf` format: literal text, or a `%` directive naming
+/// what to print.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Directive(char),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Format(Vec<Piece>);
+
+fn parse_format(s: &str) -> Result<Format> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => literal.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('0') => '\0',
+                Some('\\') => '\\',
+                Some(other) => bail!("unknown escape \"\\{other}\""),
+                None => bail!("format ends with \\"),
+            }),
+            '%' => {
+                let directive = match chars.next() {
+                    Some('%') => {
+                        literal.push('%');
+                        continue;
+                    }
+                    Some('T') if chars.next() == Some('@') => 'T',
+                    Some('T') => bail!("only %T@ is supported"),
+                    Some(d) if "pPfhdsmugUGyl".contains(d) => d,
+                    Some(d) => bail!("unknown directive \"%{d}\""),
+                    None => bail!("format ends with %"),
+                };
+                if !literal.is_empty() {
+                    pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(Piece::Directive(directive));
             }
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    Ok(Format(pieces))
+}
 
-            if (matches_name || args.names.is_empty()) 
-            && (matches_type || args.entry_types.is_empty())
-            && (
-                entry.file_type().is_dir() || entry.file_type().is_file() || entry.file_type().is_symlink()) {
-                println!("{}", entry.path().display());
+impl Format {
+    fn write(&self, entry: &DirEntry, out: &mut impl Write) -> Result<()> {
+        let path = entry.path();
+        for piece in &self.0 {
+            let directive = match piece {
+                Piece::Literal(text) => {
+                    out.write_all(text.as_bytes())?;
+                    continue;
+                }
+                Piece::Directive(directive) => *directive,
+            };
+            match directive {
+                'p' => out.write_all(path.as_os_str().as_bytes())?,
+                'P' => {
+                    let root = path.ancestors().nth(entry.depth()).unwrap_or(path);
+                    let relative = path.strip_prefix(root).unwrap_or(path);
+                    out.write_all(relative.as_os_str().as_bytes())?
+                }
+                'f' => out.write_all(entry.file_name().as_bytes())?,
+                'h' => match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => {
+                        out.write_all(parent.as_os_str().as_bytes())?
+                    }
+                    _ => out.write_all(b".")?,
+                },
+                'd' => write!(out, "{}", entry.depth())?,
+                'y' => write!(out, "{}", type_letter(entry.file_type()))?,
+                'l' => {
+                    if entry.path_is_symlink() {
+                        out.write_all(fs::read_link(path)?.as_os_str().as_bytes())?
+                    }
+                }
+                _ => {
+                    let metadata = entry.metadata()?;
+                    match directive {
+                        's' => write!(out, "{}", metadata.len())?,
+                        'm' => write!(out, "{:o}", metadata.mode() & 0o7777)?,
+                        'U' => write!(out, "{}", metadata.uid())?,
+                        'G' => write!(out, "{}", metadata.gid())?,
+                        'u' => match get_user_by_uid(metadata.uid()) {
+                            Some(user) => out.write_all(user.name().as_bytes())?,
+                            None => write!(out, "{}", metadata.uid())?,
+                        },
+                        'g' => match get_group_by_gid(metadata.gid()) {
+                            Some(group) => out.write_all(group.name().as_bytes())?,
+                            None => write!(out, "{}", metadata.gid())?,
+                        },
+                        _ => {
+                            let since = metadata
+                                .modified()?
+                                .duration_since(SystemTime::UNIX_EPOCH)
+                                .unwrap_or_default();
+                            write!(out, "{}.{:09}0", since.as_secs(), since.subsec_nanos())?
+                        }
+                    }
+                }
             }
         }
+        Ok(())
+    }
+}
+
+/// The letter `find` uses for a type of entry, as printed by `%y`.
+fn type_letter(file_type: FileType) -> char {
+    if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else {
+        'f'
+    }
+}
+
+/// An entry as reported by `--json` and `--ndjson`.
+#[derive(Debug, Serialize)]
+struct Record {
+    path: String,
+    #[serde(rename = "type")]
+    entry_type: char,
+    size: u64,
+}
+
+/// The number of entries reported by `--json` and `--ndjson`.
+#[derive(Debug, Serialize)]
+struct Total {
+    entries: usize,
+}
+
+/// Replaces each `{}` in a `--exec` argument with `path`.
+fn fill_in(arg: &str, path: &OsStr) -> OsString {
+    let mut filled = OsString::new();
+    for (i, part) in arg.split("{}").enumerate() {
+        if i > 0 {
+            filled.push(path);
+        }
+        filled.push(part);
+    }
+    filled
+}
+
+/// An action taken on the entries that reach it. Actions also act as tests:
+/// `--exec` is true when the command succeeds and the others are always true.
+#[derive(Debug)]
+enum Action {
+    Print,
+    Print0,
+    Printf(Format),
+    Exec(Vec<String>),
+    ExecBatch {
+        command: Vec<String>,
+        paths: Vec<PathBuf>,
+        bytes: usize,
+        failed: bool,
+    },
+    Delete,
+    Json {
+        report: Report,
+        entries: usize,
+    },
+}
+
+impl Action {
+    fn run(&mut self, entry: &DirEntry, out: &mut impl Write) -> Result<bool> {
+        let path = entry.path();
+        match self {
+            Action::Print => {
+                out.write_all(path.as_os_str().as_bytes())?;
+                out.write_all(b"\n")?;
+            }
+            Action::Print0 => {
+                out.write_all(path.as_os_str().as_bytes())?;
+                out.write_all(b"\0")?;
+            }
+            Action::Printf(format) => format.write(entry, out)?,
+            Action::Exec(command) => {
+                // Keep earlier output ahead of the command's own
+                out.flush()?;
+                let status = Command::new(&command[0])
+                    .args(
+                        command[1..]
+                            .iter()
+                            .map(|arg| fill_in(arg, path.as_os_str())),
+                    )
+                    .status()
+                    .map_err(|e| anyhow!("{}: {e}", command[0]))?;
+                return Ok(status.success());
+            }
+            Action::ExecBatch {
+                command,
+                paths,
+                bytes,
+                failed,
+            } => {
+                if *bytes == 0 {
+                    *bytes = command.iter().map(|word| arg_bytes(word.as_ref())).sum();
+                }
+                let len = arg_bytes(path.as_os_str());
+                if !paths.is_empty() && *bytes + len > BATCH_BYTES {
+                    *failed |= !run_batch(command, paths, out)?;
+                    *bytes = command.iter().map(|word| arg_bytes(word.as_ref())).sum();
+                }
+                *bytes += len;
+                paths.push(path.to_path_buf());
+            }
+            Action::Delete => {
+                if entry.file_type().is_dir() {
+                    fs::remove_dir(path)?
+                } else {
+                    fs::remove_file(path)?
+                }
+            }
+            Action::Json { report, entries } => {
+                let record = Record {
+                    path: path.to_string_lossy().into_owned(),
+                    entry_type: type_letter(entry.file_type()),
+                    size: entry.metadata()?.len(),
+                };
+                report.record(out, &record)?;
+                *entries += 1;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs a batched command on any paths it is still holding, or ends the
+    /// JSON report with the number of entries in it. Returns whether every
+    /// batch of the command succeeded.
+    fn finish(&mut self, out: &mut impl Write) -> Result<bool> {
+        match self {
+            Action::ExecBatch {
+                command,
+                paths,
+                bytes,
+                failed,
+            } => {
+                *failed |= !run_batch(command, paths, out)?;
+                *bytes = 0;
+                return Ok(!*failed);
+            }
+            Action::Json { report, entries } => {
+                report.finish(out, &Total { entries: *entries })?;
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+}
+
+/// Runs `command` on the batch of `paths`, emptying it, and returns whether
+/// the command succeeded.
+fn run_batch(command: &[String], paths: &mut Vec<PathBuf>, out: &mut impl Write) -> Result<bool> {
+    if paths.is_empty() {
+        return Ok(true);
+    }
+    out.flush()?;
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .args(paths.drain(..))
+        .status()
+        .map_err(|e| anyhow!("{}: {e}", command[0]))?;
+    Ok(status.success())
+}
+
+/// A single test of a directory entry.
+#[derive(Debug)]
+enum Test {
+    Name(Vec<GlobMatcher>),
+    Regex(Vec<Regex>),
+    Path(GlobMatcher),
+    Type(Vec<EntryType>),
+    Size(Size),
+    Mtime(Cmp, SystemTime),
+    Newer(SystemTime),
+    Empty,
+    Perm(Perm),
+    User(u32),
+}
+
+impl Test {
+    fn matches(&self, entry: &DirEntry) -> Result<bool> {
+        let file_type = entry.file_type();
+        let matched = match self {
+            Test::Name(globs) => globs.iter().any(|glob| glob.is_match(entry.file_name())),
+            Test::Regex(regexes) => {
+                let name = entry.file_name().to_string_lossy();
+                regexes.iter().any(|re| re.is_match(&name))
+            }
+            Test::Path(glob) => glob.is_match(entry.path()),
+            Test::Type(types) => types.iter().any(|entry_type| match entry_type {
+                EntryType::File => file_type.is_file(),
+                EntryType::Dir => file_type.is_dir(),
+                EntryType::Link => file_type.is_symlink(),
+            }),
+            Test::Size(size) => size
+                .cmp
+                .matches(entry.metadata()?.len().div_ceil(size.unit)),
+            Test::Mtime(days, now) => {
+                let age = now
+                    .duration_since(entry.metadata()?.modified()?)
+                    .unwrap_or_default();
+                days.matches(age.as_secs() / (24 * 60 * 60))
+            }
+            Test::Newer(time) => entry.metadata()?.modified()? > *time,
+            Test::Empty => {
+                if file_type.is_file() {
+                    entry.metadata()?.len() == 0
+                } else if file_type.is_dir() {
+                    fs::read_dir(entry.path())?.next().is_none()
+                } else {
+                    false
+                }
+            }
+            Test::Perm(perm) => {
+                let mode = entry.metadata()?.mode() & 0o7777;
+                match *perm {
+                    Perm::Exact(bits) => mode == bits,
+                    Perm::All(bits) => mode & bits == bits,
+                    Perm::Any(bits) => bits == 0 || mode & bits != 0,
+                }
+            }
+            Test::User(uid) => entry.metadata()?.uid() == *uid,
+        };
+        Ok(matched)
+    }
+}
+
+/// The expression built from the tests, actions and operators on the command
+/// line.
+#[derive(Debug)]
+enum Expr {
+    True,
+    Test(Test),
+    Action(Action),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&mut self, entry: &DirEntry, out: &mut impl Write) -> Result<bool> {
+        Ok(match self {
+            Expr::True => true,
+            Expr::Test(test) => test.matches(entry)?,
+            Expr::Action(action) => action.run(entry, out)?,
+            Expr::Not(expr) => !expr.eval(entry, out)?,
+            Expr::And(left, right) => left.eval(entry, out)? && right.eval(entry, out)?,
+            Expr::Or(left, right) => left.eval(entry, out)? || right.eval(entry, out)?,
+        })
+    }
+
+    fn has_action(&self) -> bool {
+        match self {
+            Expr::True | Expr::Test(_) => false,
+            Expr::Action(_) => true,
+            Expr::Not(expr) => expr.has_action(),
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                left.has_action() || right.has_action()
+            }
+        }
+    }
+
+    /// Runs the batched commands once every entry has been seen, returning
+    /// whether all of them succeeded.
+    fn finish(&mut self, out: &mut impl Write) -> Result<bool> {
+        match self {
+            Expr::True | Expr::Test(_) => Ok(true),
+            Expr::Action(action) => action.finish(out),
+            Expr::Not(expr) => expr.finish(out),
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                let left = left.finish(out)?;
+                let right = right.finish(out)?;
+                Ok(left && right)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Open,
+    Close,
+    Not,
+    And,
+    Or,
+    Test(Test),
+    Action(Action),
+}
+
+/// Lists the tests, actions and operators in the order they were given.
+/// Repeated `--name`/`--iname`, `--regex` or `--type` options with nothing
+/// between them merge into one test matching any of their values.
+fn tokens(args: &Args, matches: &ArgMatches) -> Result<Vec<Token>> {
+    fn positioned<'a, T>(
+        matches: &ArgMatches,
+        id: &str,
+        values: &'a [T],
+    ) -> impl Iterator<Item = (usize, &'a T)> {
+        matches.indices_of(id).into_iter().flatten().zip(values)
+    }
+
+    /// Splits the words of every `--exec` back into separate commands.
+    fn commands<'a>(
+        matches: &ArgMatches,
+        id: &str,
+        words: &'a [String],
+    ) -> Vec<(usize, &'a [String])> {
+        let indices: Vec<usize> = matches.indices_of(id).into_iter().flatten().collect();
+        let mut commands = Vec::new();
+        let mut start = 0;
+        for occurrence in matches.get_raw_occurrences(id).into_iter().flatten() {
+            let end = start + occurrence.len();
+            commands.push((indices[start], &words[start..end]));
+            start = end;
+        }
+        commands
+    }
+
+    let now = SystemTime::now();
+    let mut tokens: Vec<(usize, Token)> = Vec::new();
+    tokens.extend(
+        positioned(matches, "names", &args.names)
+            .chain(positioned(matches, "inames", &args.inames))
+            .map(|(i, glob)| (i, Token::Test(Test::Name(vec![glob.clone()])))),
+    );
+    tokens.extend(
+        positioned(matches, "regexes", &args.regexes)
+            .map(|(i, re)| (i, Token::Test(Test::Regex(vec![re.clone()])))),
+    );
+    tokens.extend(
+        positioned(matches, "path_globs", &args.path_globs)
+            .map(|(i, glob)| (i, Token::Test(Test::Path(glob.clone())))),
+    );
+    tokens.extend(
+        positioned(matches, "entry_types", &args.entry_types)
+            .map(|(i, &entry_type)| (i, Token::Test(Test::Type(vec![entry_type])))),
+    );
+    tokens.extend(
+        positioned(matches, "size", &args.size)
+            .map(|(i, &size)| (i, Token::Test(Test::Size(size)))),
+    );
+    tokens.extend(
+        positioned(matches, "mtime", &args.mtime)
+            .map(|(i, &days)| (i, Token::Test(Test::Mtime(days, now)))),
+    );
+    tokens.extend(
+        positioned(matches, "newer", &args.newer)
+            .map(|(i, &time)| (i, Token::Test(Test::Newer(time)))),
+    );
+    tokens.extend(
+        positioned(matches, "empty", &args.empty).map(|(i, _)| (i, Token::Test(Test::Empty))),
+    );
+    tokens.extend(
+        positioned(matches, "perm", &args.perm)
+            .map(|(i, &perm)| (i, Token::Test(Test::Perm(perm)))),
+    );
+    tokens.extend(
+        positioned(matches, "user", &args.user).map(|(i, &uid)| (i, Token::Test(Test::User(uid)))),
+    );
+    tokens.extend(
+        positioned(matches, "print", &args.print).map(|(i, _)| (i, Token::Action(Action::Print))),
+    );
+    tokens.extend(
+        positioned(matches, "print0", &args.print0)
+            .map(|(i, _)| (i, Token::Action(Action::Print0))),
+    );
+    tokens.extend(
+        positioned(matches, "printf", &args.printf)
+            .map(|(i, format)| (i, Token::Action(Action::Printf(format.clone())))),
+    );
+    tokens.extend(
+        commands(matches, "exec", &args.exec)
+            .into_iter()
+            .map(|(i, command)| (i, Token::Action(Action::Exec(command.to_vec())))),
+    );
+    for (i, command) in commands(matches, "exec_batch", &args.exec_batch) {
+        // `main` only makes a batch of commands ending in "{}"
+        let command = &command[..command.len() - 1];
+        if command.is_empty() || command.iter().any(|word| word.contains("{}")) {
+            bail!("--exec ... + takes a command and a single {{}} at the end");
+        }
+        let action = Action::ExecBatch {
+            command: command.to_vec(),
+            paths: Vec::new(),
+            bytes: 0,
+            failed: false,
+        };
+        tokens.push((i, Token::Action(action)));
+    }
+    tokens.extend(
+        positioned(matches, "delete", &args.delete)
+            .map(|(i, _)| (i, Token::Action(Action::Delete))),
+    );
+    tokens.extend(positioned(matches, "and", &args.and).map(|(i, _)| (i, Token::And)));
+    tokens.extend(positioned(matches, "or", &args.or).map(|(i, _)| (i, Token::Or)));
+    tokens.extend(positioned(matches, "not", &args.not).map(|(i, _)| (i, Token::Not)));
+    tokens.extend(positioned(matches, "open", &args.open).map(|(i, _)| (i, Token::Open)));
+    tokens.extend(positioned(matches, "close", &args.close).map(|(i, _)| (i, Token::Close)));
+    tokens.sort_by_key(|(i, _)| *i);
+
+    let mut merged: Vec<Token> = Vec::new();
+    for (_, token) in tokens {
+        match (merged.last_mut(), token) {
+            (Some(Token::Test(Test::Name(names))), Token::Test(Test::Name(more))) => {
+                names.extend(more)
+            }
+            (Some(Token::Test(Test::Regex(regexes))), Token::Test(Test::Regex(more))) => {
+                regexes.extend(more)
+            }
+            (Some(Token::Test(Test::Type(types))), Token::Test(Test::Type(more))) => {
+                types.extend(more)
+            }
+            (_, token) => merged.push(token),
+        }
+    }
+    Ok(merged)
+}
+
+/// Recursive descent parser for the expression. `--not` binds tightest, then
+/// `--and`, which is implied between adjacent tests, then `--or`.
+struct ExprParser<I: Iterator<Item = Token>> {
+    tokens: Peekable<I>,
+}
+
+impl<I: Iterator<Item = Token>> ExprParser<I> {
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.tokens.next_if(|t| matches!(t, Token::Or)).is_some() {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.not()?;
+        loop {
+            match self.tokens.peek() {
+                Some(Token::And) => {
+                    self.tokens.next();
+                }
+                Some(Token::Test(_) | Token::Action(_) | Token::Not | Token::Open) => {}
+                _ => return Ok(expr),
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        match self.tokens.next() {
+            Some(Token::Test(test)) => Ok(Expr::Test(test)),
+            Some(Token::Action(action)) => Ok(Expr::Action(action)),
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.not()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.tokens.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => bail!("invalid expression: unmatched '('"),
+                }
+            }
+            Some(Token::Close) => bail!("invalid expression: expected a test before ')'"),
+            Some(Token::And) => bail!("invalid expression: expected a test before --and"),
+            Some(Token::Or) => bail!("invalid expression: expected a test before --or"),
+            None => bail!("invalid expression: expected a test at the end"),
+        }
+    }
+}
+
+fn parse_expr(tokens: Vec<Token>) -> Result<Expr> {
+    if tokens.is_empty() {
+        return Ok(Expr::True);
+    }
+    let mut parser = ExprParser {
+        tokens: tokens.into_iter().peekable(),
+    };
+    let expr = parser.or()?;
+    if parser.tokens.next().is_some() {
+        bail!("invalid expression: unmatched ')'");
+    }
+    Ok(expr)
+}
+
+fn run(args: Args, matches: &ArgMatches) -> Result<()> {
+    let mut expr = parse_expr(tokens(&args, matches)?)?;
+    let report = if args.json {
+        Some(Report::json())
+    } else if args.ndjson {
+        Some(Report::ndjson())
+    } else {
+        None
+    };
+    // The report takes the place of the default --print, after any actions
+    if let Some(report) = report {
+        let action = Action::Json { report, entries: 0 };
+        expr = Expr::And(Box::new(expr), Box::new(Expr::Action(action)));
+    } else if !expr.has_action() {
+        expr = Expr::And(Box::new(expr), Box::new(Expr::Action(Action::Print)));
+    }
+    // Deleting a directory only works once its contents are gone
+    let contents_first = args.depth || !args.delete.is_empty();
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut failed = false;
+
+    for path in &args.paths {
+        let mut walker = WalkDir::new(path).contents_first(contents_first);
+        if let Some(depth) = args.mindepth {
+            walker = walker.min_depth(depth);
+        }
+        if let Some(depth) = args.maxdepth {
+            walker = walker.max_depth(depth);
+        }
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    eprintln!("{e}");
+                    continue;
+                }
+            };
+            if let Err(e) = expr.eval(&entry, &mut out) {
+                eprintln!("{}: {e}", entry.path().display());
+                failed = true;
+            }
+        }
+    }
+    let succeeded = expr.finish(&mut out)?;
+    out.flush()?;
+    // Like find, a failed action or "--exec ... {} +" is reported by the exit
+    // status
+    if failed || !succeeded {
+        std::process::exit(1);
     }
     Ok(())
 }
 
+/// Rewrites the arguments clap can't parse directly: "(", ")" and "!" become
+/// hidden flags, which also end the values of a preceding --name, --regex or
+/// --type, and a --exec ending in "{} +" becomes a hidden --exec-batch ending in ";".
+fn rewrite_args(args: impl Iterator<Item = OsString>) -> Result<Vec<OsString>> {
+    let mut rewritten: Vec<OsString> = Vec::new();
+    let mut exec_start = None;
+    for arg in args {
+        if let Some(start) = exec_start {
+            if arg == "+" && rewritten.last().is_some_and(|last| last == "{}") {
+                rewritten[start] = "--exec-batch".into();
+                rewritten.push(";".into());
+                exec_start = None;
+                continue;
+            }
+            if arg == ";" {
+                exec_start = None;
+            }
+            rewritten.push(arg);
+            continue;
+        }
+        rewritten.push(match arg.to_str() {
+            Some("(") => "--(".into(),
+            Some(")") => "--)".into(),
+            Some("!") => "--not".into(),
+            Some("--exec") => {
+                exec_start = Some(rewritten.len());
+                arg
+            }
+            _ => arg,
+        });
+    }
+    if exec_start.is_some() {
+        bail!("--exec needs a terminating ';' or '{{}} +'");
+    }
+    Ok(rewritten)
+}
+
 fn main() {
-    if let Err(e) = run(Args::parse()) {
+    let matches = match rewrite_args(std::env::args_os()) {
+        Ok(argv) => Args::command().get_matches_from(argv),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Err(e) = run(args, &matches) {
         eprintln!("{}", e);
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Cmp, Format, Perm, Piece, Size, fill_in, parse_days, parse_format, parse_glob, parse_iglob,
+        parse_perm, parse_size,
+    };
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_parse_size() {
+        let size = |cmp, unit| Size { cmp, unit };
+        assert_eq!(parse_size("3").unwrap(), size(Cmp::Equal(3), 512));
+        assert_eq!(parse_size("+10k").unwrap(), size(Cmp::Greater(10), 1024));
+        assert_eq!(parse_size("-2M").unwrap(), size(Cmp::Less(2), 1 << 20));
+        assert_eq!(parse_size("7c").unwrap(), size(Cmp::Equal(7), 1));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("k").is_err());
+        assert!(parse_size("3x").is_err());
+        assert!(parse_size("+-3").is_err());
+    }
+
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(parse_days("0").unwrap(), Cmp::Equal(0));
+        assert_eq!(parse_days("+7").unwrap(), Cmp::Greater(7));
+        assert_eq!(parse_days("-1").unwrap(), Cmp::Less(1));
+        assert!(parse_days("1d").is_err());
+    }
+
+    #[test]
+    fn test_cmp_matches() {
+        assert!(Cmp::Greater(1).matches(2));
+        assert!(!Cmp::Greater(1).matches(1));
+        assert!(Cmp::Less(1).matches(0));
+        assert!(!Cmp::Less(1).matches(1));
+        assert!(Cmp::Equal(1).matches(1));
+    }
+
+    #[test]
+    fn test_parse_perm() {
+        assert_eq!(parse_perm("644").unwrap(), Perm::Exact(0o644));
+        assert_eq!(parse_perm("-4000").unwrap(), Perm::All(0o4000));
+        assert_eq!(parse_perm("/111").unwrap(), Perm::Any(0o111));
+        assert!(parse_perm("999").is_err());
+        assert!(parse_perm("17777").is_err());
+        assert!(parse_perm("u+x").is_err());
+    }
+
+    #[test]
+    fn test_parse_glob() {
+        let glob = parse_glob("*.rs").unwrap();
+        assert!(glob.is_match("main.rs"));
+        assert!(glob.is_match(".hidden.rs"));
+        assert!(glob.is_match("src/main.rs"));
+        assert!(!glob.is_match("main.RS"));
+        assert!(parse_iglob("*.rs").unwrap().is_match("main.RS"));
+        assert!(parse_glob("f?[!x].txt").unwrap().is_match("foo.txt"));
+        assert!(parse_glob("[a").is_err());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        let literal = |s: &str| Piece::Literal(s.to_string());
+        assert_eq!(
+            parse_format("%p\\n").unwrap(),
+            Format(vec![Piece::Directive('p'), literal("\n")])
+        );
+        assert_eq!(
+            parse_format("size=%s%%\\t%T@\\0").unwrap(),
+            Format(vec![
+                literal("size="),
+                Piece::Directive('s'),
+                literal("%\t"),
+                Piece::Directive('T'),
+                literal("\0"),
+            ])
+        );
+        assert_eq!(parse_format("").unwrap(), Format(vec![]));
+        assert!(parse_format("%").is_err());
+        assert!(parse_format("%q").is_err());
+        assert!(parse_format("%Tk").is_err());
+        assert!(parse_format("\\q").is_err());
+        assert!(parse_format("\\").is_err());
+    }
+
+    #[test]
+    fn test_fill_in() {
+        let path = OsStr::new("a/b.txt");
+        assert_eq!(fill_in("{}", path), "a/b.txt");
+        assert_eq!(fill_in("--file={}.bak", path), "--file=a/b.txt.bak");
+        assert_eq!(fill_in("{}:{}", path), "a/b.txt:a/b.txt");
+        assert_eq!(fill_in("plain", path), "plain");
+    }
+}
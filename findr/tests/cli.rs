@@ -3,7 +3,11 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{Rng, distr::Alphanumeric};
+use std::fs::File;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::time::{Duration, SystemTime};
 use std::{borrow::Cow, fs, path::Path};
+use tempfile::TempDir;
 
 const PRG: &str = "findr";
 
@@ -39,13 +43,24 @@ fn skips_bad_dir() -> Result<()> {
 #[test]
 fn dies_bad_name() -> Result<()> {
     Command::cargo_bin(PRG)?
-        .args(["--name", "*.csv"])
+        .args(["--regex", "*.csv"])
         .assert()
         .failure()
         .stderr(predicate::str::contains("error: invalid value '*.csv'"));
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_bad_glob() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--name", "[a"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error: invalid value '[a'"));
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn dies_bad_type() -> Result<()> {
@@ -60,14 +75,14 @@ fn dies_bad_type() -> Result<()> {
 
 // --------------------------------------------------
 #[cfg(windows)]
-fn format_file_name(expected_file: &str) -> Cow<str> {
+fn format_file_name(expected_file: &str) -> Cow<'_, str> {
     // Equivalent to: Cow::Owned(format!("{}.windows", expected_file))
     format!("{}.windows", expected_file).into()
 }
 
 // --------------------------------------------------
 #[cfg(not(windows))]
-fn format_file_name(expected_file: &str) -> Cow<str> {
+fn format_file_name(expected_file: &str) -> Cow<'_, str> {
     // Equivalent to: Cow::Borrowed(expected_file)
     expected_file.into()
 }
@@ -226,7 +241,7 @@ fn type_f_l() -> Result<()> {
 #[test]
 fn name_csv() -> Result<()> {
     run(
-        &["tests/inputs", "-n", ".*[.]csv"],
+        &["tests/inputs", "-n", "*.csv"],
         "tests/expected/name_csv.txt",
     )
 }
@@ -235,7 +250,7 @@ fn name_csv() -> Result<()> {
 #[test]
 fn name_csv_mp3() -> Result<()> {
     run(
-        &["tests/inputs", "-n", ".*[.]csv", "-n", ".*[.]mp3"],
+        &["tests/inputs", "-n", "*.csv", "-n", "*.mp3"],
         "tests/expected/name_csv_mp3.txt",
     )
 }
@@ -244,22 +259,61 @@ fn name_csv_mp3() -> Result<()> {
 #[test]
 fn name_txt_path_a_d() -> Result<()> {
     run(
-        &["tests/inputs/a", "tests/inputs/d", "--name", ".*.txt"],
+        &["tests/inputs/a", "tests/inputs/d", "--name", "*.txt"],
         "tests/expected/name_txt_path_a_d.txt",
     )
 }
 
+// --------------------------------------------------
+#[test]
+fn name_exact() -> Result<()> {
+    run(
+        &["tests/inputs", "-n", "b.csv", "-n", "?"],
+        "tests/expected/name_exact.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn iname_csv() -> Result<()> {
+    run(
+        &["tests/inputs", "--iname", "*.CSV"],
+        "tests/expected/name_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_csv() -> Result<()> {
+    run(
+        &["tests/inputs", "--regex", "[.]csv$"],
+        "tests/expected/name_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn path_star_crosses_slash() -> Result<()> {
+    run(
+        &["tests/inputs", "--path", "*/d/*", "--path", "*.txt"],
+        "tests/expected/path_d_txt.txt",
+    )
+}
+
 // --------------------------------------------------
 #[test]
 fn name_a() -> Result<()> {
-    run(&["tests/inputs", "-n", "a"], "tests/expected/name_a.txt")
+    run(
+        &["tests/inputs", "--regex", "a"],
+        "tests/expected/name_a.txt",
+    )
 }
 
 // --------------------------------------------------
 #[test]
 fn type_f_name_a() -> Result<()> {
     run(
-        &["tests/inputs", "-t", "f", "-n", "a"],
+        &["tests/inputs", "-t", "f", "--regex", "a"],
         "tests/expected/type_f_name_a.txt",
     )
 }
@@ -268,7 +322,7 @@ fn type_f_name_a() -> Result<()> {
 #[test]
 fn type_d_name_a() -> Result<()> {
     run(
-        &["tests/inputs", "--type", "d", "--name", "a"],
+        &["tests/inputs", "--type", "d", "--regex", "a"],
         "tests/expected/type_d_name_a.txt",
     )
 }
@@ -279,6 +333,410 @@ fn path_g() -> Result<()> {
     run(&["tests/inputs/g.csv"], "tests/expected/path_g.txt")
 }
 
+// --------------------------------------------------
+#[test]
+fn maxdepth_1() -> Result<()> {
+    run(
+        &["tests/inputs", "--maxdepth", "1"],
+        "tests/expected/maxdepth_1.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mindepth_2_maxdepth_2() -> Result<()> {
+    run(
+        &["tests/inputs", "--mindepth", "2", "--maxdepth", "2"],
+        "tests/expected/mindepth_2_maxdepth_2.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn type_f_name_csv_or_mp3() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "-t",
+            "f",
+            "(",
+            "-n",
+            "*.csv",
+            "--or",
+            "-n",
+            "*.mp3",
+            ")",
+        ],
+        "tests/expected/type_f_name_csv_or_mp3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn not_type_d() -> Result<()> {
+    run(
+        &["tests/inputs", "!", "-t", "d"],
+        "tests/expected/not_type_d.txt",
+    )?;
+    run(
+        &["tests/inputs", "--not", "--type", "d"],
+        "tests/expected/not_type_d.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn and_binds_tighter_than_or() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "-n",
+            "*.txt",
+            "--or",
+            "-n",
+            "*.csv",
+            "-t",
+            "l",
+        ],
+        "tests/expected/name_txt_or_csv_type_l.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn not_group_and_name() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "!",
+            "(",
+            "-t",
+            "d",
+            "--or",
+            "-n",
+            "*.txt",
+            ")",
+            "--and",
+            "-n",
+            "b*",
+        ],
+        "tests/expected/not_d_or_txt_name_b.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_expression() -> Result<()> {
+    for (args, expected) in [
+        (&["(", "-t", "f"][..], "unmatched '('"),
+        (&["-t", "f", ")"], "unmatched ')'"),
+        (&["--or", "-t", "f"], "expected a test before --or"),
+        (&["-t", "f", "--and"], "expected a test at the end"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(expected));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_size() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--size", "3x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "error: invalid value '3x' for '--size",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn printf() -> Result<()> {
+    run(
+        &["tests/inputs", "--printf", "%y %d %h %f %P\\n"],
+        "tests/expected/printf_y_d_h_f_P.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn print0() -> Result<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-t", "f", "l", "--print0"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.ends_with('\0'));
+    let mut paths: Vec<&str> = stdout.split_terminator('\0').collect();
+    paths.sort();
+
+    let expected = fs::read_to_string("tests/expected/type_f_l.txt")?;
+    let mut expected: Vec<&str> = expected.lines().collect();
+    expected.sort();
+    assert_eq!(paths, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_each() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "-t",
+            "f",
+            "--exec",
+            "echo",
+            "found",
+            "{}",
+            ";",
+        ],
+        "tests/expected/type_f_exec_echo.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_as_test() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "--exec",
+            "test",
+            "-L",
+            "{}",
+            ";",
+            "--or",
+            "-n",
+            "c*",
+            "--print",
+        ],
+        "tests/expected/exec_test_link_or_name_c.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_batch() -> Result<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args([
+            "tests/inputs",
+            "-t",
+            "f",
+            "--exec",
+            "echo",
+            "batch:",
+            "{}",
+            "+",
+        ])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let mut paths: Vec<&str> = lines[0].split(' ').collect();
+    assert_eq!(paths.remove(0), "batch:");
+    paths.sort();
+
+    let expected = fs::read_to_string("tests/expected/type_f.txt")?;
+    let mut expected: Vec<&str> = expected.lines().collect();
+    expected.sort();
+    assert_eq!(paths, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_batch_fails() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-t", "f", "--exec", "false", "{}", "+"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_batch_splits_long_lists() -> Result<()> {
+    // 1,000 paths of over 200 bytes each are too many for one command line
+    let dir = TempDir::new()?;
+    let stem = "x".repeat(200);
+    for i in 0..1000 {
+        File::create(dir.path().join(format!("{stem}{i}")))?;
+    }
+    let output = Command::cargo_bin(PRG)?
+        .args([dir.path().to_str().unwrap(), "-t", "f"])
+        .args(["--exec", "sh", "-c", "echo $#", "sh", "{}", "+"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let counts: Vec<usize> = stdout.lines().map(|line| line.parse().unwrap()).collect();
+    assert!(counts.len() > 1);
+    assert_eq!(counts.iter().sum::<usize>(), 1000);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_exec() -> Result<()> {
+    for (args, expected) in [
+        (
+            &["--exec", "echo", "{}"][..],
+            "--exec needs a terminating ';'",
+        ),
+        (
+            &["--exec", "echo", "{}", "{}", "+"],
+            "a single {} at the end",
+        ),
+        (&["--exec", ";"], "a value is required for '--exec"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(expected));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn depth_first() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "--depth", "-n", "[abc]"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/a/b/c\ntests/inputs/a/b\ntests/inputs/a\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete() -> Result<()> {
+    let dir = TempDir::new()?;
+    let root = dir.path();
+    fs::create_dir_all(root.join("tmp/nested"))?;
+    fs::create_dir(root.join("keep"))?;
+    fs::write(root.join("tmp/nested/a.log"), "")?;
+    fs::write(root.join("tmp/b.log"), "")?;
+    fs::write(root.join("keep/c.log"), "")?;
+    fs::write(root.join("keep/d.txt"), "")?;
+
+    // Removing a directory tree only works contents first
+    let tmp = root.join("tmp");
+    Command::cargo_bin(PRG)?
+        .args([tmp.to_str().unwrap(), "--delete"])
+        .assert()
+        .success()
+        .stdout("");
+    assert!(!tmp.exists());
+
+    Command::cargo_bin(PRG)?
+        .arg(root)
+        .args(["-n", "*.log", "--delete", "--print"])
+        .assert()
+        .success()
+        .stdout(format!("{}/keep/c.log\n", root.display()));
+    assert_eq!(find_in(root, &[])?, ["keep", "keep/d.txt"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_non_empty_dir_fails() -> Result<()> {
+    let dir = TempDir::new()?;
+    let root = dir.path();
+    fs::create_dir(root.join("keep"))?;
+    fs::write(root.join("keep/a.txt"), "")?;
+
+    // The failure is reported, the walk goes on, and the exit status says so
+    Command::cargo_bin(PRG)?
+        .arg(root)
+        .args(["-n", "keep", "--delete", "--or", "-n", "*.txt", "--print"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(format!("{}/keep/a.txt\n", root.display()))
+        .stderr(predicate::str::contains("keep: "));
+    assert!(root.join("keep/a.txt").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+fn find_in(dir: &Path, args: &[&str]) -> Result<Vec<String>> {
+    let output = Command::cargo_bin(PRG)?.arg(dir).args(args).output()?;
+    assert!(output.status.success());
+    let prefix = format!("{}/", dir.display());
+    let mut names: Vec<String> = String::from_utf8(output.stdout)?
+        .lines()
+        .filter_map(|line| line.strip_prefix(&prefix).map(str::to_string))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+// --------------------------------------------------
+#[test]
+fn metadata_tests() -> Result<()> {
+    let dir = TempDir::new()?;
+    let root = dir.path();
+    fs::create_dir(root.join("empty_dir"))?;
+    fs::create_dir(root.join("full_dir"))?;
+    fs::write(root.join("empty.txt"), "")?;
+    fs::write(root.join("small.txt"), vec![b'x'; 1000])?;
+    fs::write(root.join("full_dir/big.txt"), vec![b'x'; 3000])?;
+
+    let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+    File::options()
+        .write(true)
+        .open(root.join("small.txt"))?
+        .set_modified(week_ago)?;
+    fs::set_permissions(root.join("small.txt"), fs::Permissions::from_mode(0o600))?;
+    fs::set_permissions(
+        root.join("full_dir/big.txt"),
+        fs::Permissions::from_mode(0o755),
+    )?;
+    fs::set_permissions(root.join("empty.txt"), fs::Permissions::from_mode(0o644))?;
+
+    let files = ["-t", "f"];
+    let find = |args: &[&str]| find_in(root, &[&files[..], args].concat());
+
+    assert_eq!(find(&["--size", "-1k"])?, ["empty.txt"]);
+    assert_eq!(find(&["--size", "1k"])?, ["small.txt"]);
+    assert_eq!(find(&["--size", "+1k"])?, ["full_dir/big.txt"]);
+    assert_eq!(find(&["--size", "3000c"])?, ["full_dir/big.txt"]);
+    assert_eq!(find(&["--size", "+2"])?, ["full_dir/big.txt"]);
+    assert_eq!(find(&["--mtime", "+6"])?, ["small.txt"]);
+    assert_eq!(find(&["--mtime", "-1"])?, ["empty.txt", "full_dir/big.txt"]);
+    assert_eq!(
+        find(&["--newer", root.join("small.txt").to_str().unwrap()])?,
+        ["empty.txt", "full_dir/big.txt"]
+    );
+    assert_eq!(find(&["--perm", "600"])?, ["small.txt"]);
+    assert_eq!(
+        find(&["--perm", "-644"])?,
+        ["empty.txt", "full_dir/big.txt"]
+    );
+    assert_eq!(find(&["--perm", "/111"])?, ["full_dir/big.txt"]);
+
+    assert_eq!(find_in(root, &["--empty"])?, ["empty.txt", "empty_dir"]);
+    assert_eq!(
+        find_in(root, &["--not", "--empty", "--maxdepth", "1"])?,
+        ["full_dir", "small.txt"]
+    );
+
+    let uid = fs::metadata(root.join("empty.txt"))?.uid().to_string();
+    assert_eq!(find(&["--user", &uid])?.len(), 3);
+    assert!(find(&["--user", "4294967294"])?.is_empty());
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 #[cfg(not(windows))]
@@ -309,3 +767,59 @@ fn unreadable_dir() -> Result<()> {
     assert!(stderr.contains("cant-touch-this: Permission denied"));
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn json() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/g.csv", "--json"])
+        .assert()
+        .success()
+        .stdout(concat!(
+            r#"{"files":[{"path":"tests/inputs/g.csv","type":"f","size":2}],"#,
+            r#""total":{"entries":1}}"#,
+            "\n"
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ndjson() -> Result<()> {
+    let output = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-t", "f", "l", "--ndjson"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.pop(), Some(r#"{"total":{"entries":9}}"#));
+    lines.sort();
+
+    let expected = fs::read_to_string("tests/expected/type_f_l.txt")?;
+    let expected: Vec<String> = expected
+        .lines()
+        .map(|path| {
+            let (entry_type, size) = if path == "tests/inputs/d/b.csv" {
+                ("l", 12)
+            } else {
+                ("f", 2)
+            };
+            format!(r#"{{"path":"{path}","type":"{entry_type}","size":{size}}}"#)
+        })
+        .collect();
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_json_and_print() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--json", "--print"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--json' cannot be used with '--print'",
+        ));
+    Ok(())
+}
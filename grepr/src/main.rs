@@ -1,8 +1,22 @@
-use anyhow::Result;
-use clap::Parser;
-use clir::open;
-use regex::{Regex, RegexBuilder};
-use walkdir::WalkDir;
+use anyhow::{Result, anyhow};
+use clap::{Parser, ValueEnum};
+use clir::{Report, open};
+use ignore::{WalkBuilder, WalkState};
+use regex::bytes::{Match, Regex, RegexBuilder};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::thread;
+use std::{env, fs};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -16,88 +30,776 @@ struct Args {
     recursive: bool,
     #[arg(short, long("count"))]
     counts: bool,
+    #[arg(short('A'), long, value_name = "NUM")]
+    after_context: Option<usize>,
+    #[arg(short('B'), long, value_name = "NUM")]
+    before_context: Option<usize>,
+    #[arg(short('C'), long, value_name = "NUM")]
+    context: Option<usize>,
+    #[arg(short('n'), long)]
+    line_number: bool,
+    #[arg(short('b'), long)]
+    byte_offset: bool,
+    #[arg(short('l'), long, conflicts_with = "files_without_match")]
+    files_with_matches: bool,
+    #[arg(short('L'), long)]
+    files_without_match: bool,
+    #[arg(short('v'), long)]
+    invert_match: bool,
+    #[arg(short('o'), long)]
+    only_matching: bool,
+    #[arg(short('w'), long, conflicts_with = "line_regexp")]
+    word_regexp: bool,
+    #[arg(short('x'), long)]
+    line_regexp: bool,
+    #[arg(short('m'), long, value_name = "NUM")]
+    max_count: Option<usize>,
+    /// Search binary files found while recursing instead of skipping them
+    #[arg(short('a'), long)]
+    text: bool,
+    /// Search hidden files and directories when recursing
+    #[arg(long)]
+    hidden: bool,
+    /// Don't respect .gitignore and .ignore files when recursing
+    #[arg(long)]
+    no_ignore: bool,
+    /// When to color matches and prefixes; auto colors only a terminal and
+    /// honours NO_COLOR
+    #[arg(
+        long,
+        alias = "colour",
+        value_name = "WHEN",
+        value_enum,
+        default_value = "auto",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "auto"
+    )]
+    color: ColorChoice,
+    /// Print the counts as one JSON document; requires --count
+    #[arg(long, requires = "counts", conflicts_with = "ndjson")]
+    json: bool,
+    /// Print the counts as a JSON object per line; requires --count
+    #[arg(long, requires = "counts")]
+    ndjson: bool,
 }
 
-fn print_file_stats(
-    needle: &Regex,
-    file_name: &str,
-    file_count: usize,
-    show_counts: bool,
-) -> Result<()> {
-    let mut file = open(&file_name)?;
-    let mut line = String::new();
-    let mut matching_lines = Vec::new();
-    loop {
-        let bytes_read = file.read_line(&mut line)?;
-        if bytes_read == 0 {
-            break;
+impl Args {
+    fn context_lines(&self) -> (usize, usize) {
+        (
+            self.before_context.or(self.context).unwrap_or(0),
+            self.after_context.or(self.context).unwrap_or(0),
+        )
+    }
+
+    fn lists_files(&self) -> bool {
+        self.files_with_matches || self.files_without_match
+    }
+
+    /// The JSON report counts go to, or `None` to print them as text.
+    fn report(&self) -> Option<Report> {
+        if self.json {
+            Some(Report::json())
+        } else if self.ndjson {
+            Some(Report::ndjson())
+        } else {
+            None
+        }
+    }
+
+    /// Whether lines are printed at all, rather than counts or file names.
+    fn prints_lines(&self) -> bool {
+        !self.counts && !self.lists_files()
+    }
+
+    /// The colors to use, read from `GREP_COLORS`, or `None` for plain output.
+    fn colors(&self) -> Option<Colors> {
+        let enabled = match self.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal()
+                    && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && env::var_os("TERM").is_none_or(|term| term != "dumb")
+            }
+        };
+        enabled.then(|| Colors::parse(&env::var("GREP_COLORS").unwrap_or_default()))
+    }
+}
+
+/// SGR sequences for each part of the output, using GNU grep's
+/// `GREP_COLORS` capability names. An empty sequence leaves that part plain.
+#[derive(Debug, PartialEq)]
+struct Colors {
+    selected_match: String,
+    context_match: String,
+    selected_line: String,
+    context_line: String,
+    file_name: String,
+    line_number: String,
+    byte_offset: String,
+    separator: String,
+    /// Whether `sl` and `cx` swap meaning under `-v`
+    reverse: bool,
+    /// Whether each sequence is followed by Erase in Line (`\x1b[K`)
+    erase: bool,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            selected_match: "01;31".to_string(),
+            context_match: "01;31".to_string(),
+            selected_line: String::new(),
+            context_line: String::new(),
+            file_name: "35".to_string(),
+            line_number: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+            reverse: false,
+            erase: true,
+        }
+    }
+}
+
+impl Colors {
+    /// Applies a `GREP_COLORS` value such as `ms=01;31:fn=35:ne` over the
+    /// defaults. Unknown capabilities are ignored and, as in GNU grep, a
+    /// malformed sequence ends parsing.
+    fn parse(spec: &str) -> Self {
+        let mut colors = Self::default();
+        for capability in spec.split(':') {
+            let Some((name, sgr)) = capability.split_once('=') else {
+                match capability {
+                    "rv" => colors.reverse = true,
+                    "ne" => colors.erase = false,
+                    _ => {}
+                }
+                continue;
+            };
+            if !sgr.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+                break;
+            }
+            let sgr = sgr.to_string();
+            match name {
+                "mt" => {
+                    colors.selected_match = sgr.clone();
+                    colors.context_match = sgr;
+                }
+                "ms" => colors.selected_match = sgr,
+                "mc" => colors.context_match = sgr,
+                "sl" => colors.selected_line = sgr,
+                "cx" => colors.context_line = sgr,
+                "fn" => colors.file_name = sgr,
+                "ln" => colors.line_number = sgr,
+                "bn" => colors.byte_offset = sgr,
+                "se" => colors.separator = sgr,
+                _ => {}
+            }
         }
+        colors
+    }
 
-        if needle.is_match(&line) {
-            let trans = line.clone();
-            matching_lines.push(trans.to_string());
+    fn start(&self, out: &mut impl Write, sgr: &str) -> io::Result<()> {
+        if !sgr.is_empty() {
+            write!(out, "\x1b[{sgr}m")?;
+            if self.erase {
+                out.write_all(b"\x1b[K")?;
+            }
         }
-        line.clear();
+        Ok(())
     }
 
-    if show_counts {
-        if file_count > 1 {
-            println!("{}:{}", file_name, matching_lines.iter().count());
+    fn end(&self, out: &mut impl Write, sgr: &str) -> io::Result<()> {
+        if !sgr.is_empty() {
+            out.write_all(b"\x1b[m")?;
+            if self.erase {
+                out.write_all(b"\x1b[K")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn paint(&self, out: &mut impl Write, sgr: &str, text: &[u8]) -> io::Result<()> {
+        self.start(out, sgr)?;
+        out.write_all(text)?;
+        self.end(out, sgr)
+    }
+}
+
+/// Writes the `--` line between groups of lines that are not adjacent.
+fn write_group_separator(out: &mut impl Write, colors: Option<&Colors>) -> io::Result<()> {
+    match colors {
+        Some(colors) => colors.paint(out, &colors.separator, b"--")?,
+        None => out.write_all(b"--")?,
+    }
+    writeln!(out)
+}
+
+/// Writes output lines for one file, prefixing them with the file name, line
+/// number and byte offset as requested, and emitting `--` between groups of
+/// lines that are not adjacent.
+struct Printer<'a, W: Write> {
+    out: &'a mut W,
+    needle: &'a Needle,
+    colors: Option<&'a Colors>,
+    file_name: Option<&'a str>,
+    line_number: bool,
+    byte_offset: bool,
+    only_matching: bool,
+    invert_match: bool,
+    context: bool,
+    last_line: Option<usize>,
+}
+
+impl<'a, W: Write> Printer<'a, W> {
+    fn line(
+        &mut self,
+        line_number: usize,
+        offset: usize,
+        line: &[u8],
+        separator: char,
+    ) -> Result<()> {
+        if self.context && self.last_line.is_some_and(|last| last + 1 < line_number) {
+            write_group_separator(self.out, self.colors)?;
+        }
+        self.last_line = Some(line_number);
+        let sep = separator.to_string();
+        if let Some(file_name) = self.file_name {
+            self.paint(|colors| &colors.file_name, file_name.as_bytes())?;
+            self.paint(|colors| &colors.separator, sep.as_bytes())?;
+        }
+        if self.line_number {
+            self.paint(
+                |colors| &colors.line_number,
+                line_number.to_string().as_bytes(),
+            )?;
+            self.paint(|colors| &colors.separator, sep.as_bytes())?;
+        }
+        if self.byte_offset {
+            self.paint(|colors| &colors.byte_offset, offset.to_string().as_bytes())?;
+            self.paint(|colors| &colors.separator, sep.as_bytes())?;
+        }
+        let text = clir::chomp(line);
+        match self.colors {
+            Some(colors) => self.highlight(colors, text, separator == ':')?,
+            None => self.out.write_all(text)?,
+        }
+        self.out.write_all(&line[text.len()..])?;
+        if !line.ends_with(b"\n") {
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `text` in the color `pick` selects, or plainly without colors.
+    fn paint(&mut self, pick: fn(&Colors) -> &str, text: &[u8]) -> io::Result<()> {
+        match self.colors {
+            Some(colors) => colors.paint(self.out, pick(colors), text),
+            None => self.out.write_all(text),
+        }
+    }
+
+    /// Writes a selected or context line in its line color. Matches are
+    /// highlighted only in lines that matched, which with `-v` are the context
+    /// lines.
+    fn highlight(&mut self, colors: &Colors, text: &[u8], selected: bool) -> io::Result<()> {
+        let line_sgr = if selected != (colors.reverse && self.invert_match) {
+            &colors.selected_line
         } else {
-            println!("{}", matching_lines.iter().count());
+            &colors.context_line
+        };
+        let match_sgr = if selected {
+            &colors.selected_match
+        } else {
+            &colors.context_match
+        };
+        if self.only_matching {
+            return colors.paint(self.out, match_sgr, text);
         }
+        let mut last = 0;
+        if selected != self.invert_match {
+            for found in self.needle.find_iter(text).filter(|m| !m.is_empty()) {
+                colors.start(self.out, line_sgr)?;
+                self.out.write_all(&text[last..found.start()])?;
+                colors.paint(self.out, match_sgr, found.as_bytes())?;
+                last = found.end();
+            }
+        }
+        if last < text.len() {
+            colors.paint(self.out, line_sgr, &text[last..])?;
+        }
+        Ok(())
+    }
+}
+
+/// The pattern to search for, which with `-w` only matches whole words.
+struct Needle {
+    regex: Regex,
+    word: bool,
+}
+
+impl Needle {
+    fn is_match(&self, text: &[u8]) -> bool {
+        if self.word {
+            self.find_iter(text).next().is_some()
+        } else {
+            self.regex.is_match(text)
+        }
+    }
+
+    /// The matches in `text` from left to right. With `-w`, a match next to
+    /// a word character is passed over, and the search resumes one byte
+    /// after where it started.
+    fn find_iter<'t>(&'t self, text: &'t [u8]) -> impl Iterator<Item = Match<'t>> + 't {
+        let mut at = 0;
+        std::iter::from_fn(move || {
+            while at <= text.len() {
+                let found = self.regex.find_at(text, at)?;
+                if !self.word
+                    || !(word_before(text, found.start()) || word_after(text, found.end()))
+                {
+                    at = found.end() + usize::from(found.is_empty());
+                    return Some(found);
+                }
+                at = found.start() + 1;
+            }
+            None
+        })
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether the character ending at `at` in `text` is a word character.
+fn word_before(text: &[u8], at: usize) -> bool {
+    text[at.saturating_sub(4)..at]
+        .utf8_chunks()
+        .last()
+        .filter(|chunk| chunk.invalid().is_empty())
+        .and_then(|chunk| chunk.valid().chars().next_back())
+        .is_some_and(is_word_char)
+}
+
+/// Whether the character starting at `at` in `text` is a word character.
+fn word_after(text: &[u8], at: usize) -> bool {
+    text[at..(at + 4).min(text.len())]
+        .utf8_chunks()
+        .next()
+        .and_then(|chunk| chunk.valid().chars().next())
+        .is_some_and(is_word_char)
+}
+
+/// A file's count of selected lines, or the total over every file when
+/// `file` is `None`, as reported by `--json` and `--ndjson`.
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    count: usize,
+}
+
+/// A file to search, and whether it was found by walking a directory rather
+/// than named on the command line.
+struct Target {
+    name: String,
+    walked: bool,
+}
+
+/// How `file_name` is shown in output and reports, naming stdin as GNU grep
+/// does.
+fn label(file_name: &str) -> &str {
+    if file_name == "-" {
+        "(standard input)"
+    } else {
+        file_name
+    }
+}
+
+fn print_file_stats(
+    needle: &Needle,
+    file: impl BufRead,
+    file_name: &str,
+    show_names: bool,
+    colors: Option<&Colors>,
+    args: &Args,
+    out: &mut impl Write,
+) -> Result<usize> {
+    let label = label(file_name);
+    let (before, after) = args.context_lines();
+    let mut printer = Printer {
+        out,
+        needle,
+        colors,
+        file_name: show_names.then_some(label),
+        line_number: args.line_number,
+        byte_offset: args.byte_offset,
+        only_matching: args.only_matching,
+        invert_match: args.invert_match,
+        context: before > 0 || after > 0,
+        last_line: None,
+    };
+    // Only matches are printed with -o, but groups are still separated
+    let (before, after) = if args.only_matching {
+        (0, 0)
     } else {
-        for ml in matching_lines {
-            if file_count > 1 {
-                print!("{}:{}", file_name, ml);
+        (before, after)
+    };
+    let mut count = 0;
+    let mut offset = 0;
+    let mut pending: VecDeque<(usize, usize, Vec<u8>)> = VecDeque::new();
+    let mut after_left = 0;
+
+    for (idx, line) in clir::lines(file).enumerate() {
+        let line = line?;
+        let line_number = idx + 1;
+        let line_offset = offset;
+        offset += line.len();
+
+        if args.max_count.is_some_and(|max| count >= max) {
+            // Trailing context is still printed after the last allowed match
+            if after_left == 0 || !args.prints_lines() {
+                break;
+            }
+            after_left -= 1;
+            printer.line(line_number, line_offset, &line, '-')?;
+            continue;
+        }
+
+        let content = clir::chomp(&line);
+        if needle.is_match(content) != args.invert_match {
+            count += 1;
+            if args.lists_files() {
+                break;
+            }
+            if args.counts {
+                continue;
+            }
+            for (number, context_offset, context_line) in pending.drain(..) {
+                printer.line(number, context_offset, &context_line, '-')?;
+            }
+            if args.only_matching {
+                for found in needle.find_iter(content).filter(|m| !m.is_empty()) {
+                    printer.line(
+                        line_number,
+                        line_offset + found.start(),
+                        found.as_bytes(),
+                        ':',
+                    )?;
+                }
             } else {
-                print!("{}", ml);
+                printer.line(line_number, line_offset, &line, ':')?;
+            }
+            after_left = after;
+        } else if after_left > 0 {
+            after_left -= 1;
+            printer.line(line_number, line_offset, &line, '-')?;
+        } else if before > 0 {
+            if pending.len() == before {
+                pending.pop_front();
             }
+            pending.push_back((line_number, line_offset, line));
         }
     }
-    Ok(())
+
+    if args.files_with_matches {
+        if count > 0 {
+            printer.paint(|colors| &colors.file_name, label.as_bytes())?;
+            writeln!(printer.out)?;
+        }
+    } else if args.files_without_match {
+        if count == 0 {
+            printer.paint(|colors| &colors.file_name, label.as_bytes())?;
+            writeln!(printer.out)?;
+        }
+    } else if args.counts && !args.json && !args.ndjson {
+        if show_names {
+            printer.paint(|colors| &colors.file_name, label.as_bytes())?;
+            printer.paint(|colors| &colors.separator, b":")?;
+        }
+        writeln!(printer.out, "{}", count)?;
+    }
+    Ok(count)
+}
+
+/// Treats a file as binary if its first buffer holds a NUL byte, as GNU grep
+/// does.
+fn is_binary(file: &mut impl BufRead) -> io::Result<bool> {
+    Ok(file.fill_buf()?.contains(&0))
+}
+
+/// What searching one file while walking produced: its output, and its count
+/// of selected lines unless it was skipped as binary.
+struct Searched {
+    output: Vec<u8>,
+    count: Option<usize>,
+}
+
+/// Searches one file, writing its output to `out` and returning its count of
+/// selected lines unless it was skipped as binary. Binary files found while
+/// recursing produce no output unless `--text` is given.
+fn search_file(
+    needle: &Needle,
+    target: &Target,
+    show_names: bool,
+    colors: Option<&Colors>,
+    args: &Args,
+    out: &mut impl Write,
+) -> Result<Option<usize>> {
+    let mut file = open(&target.name)?;
+    if target.walked && !args.text && is_binary(&mut file)? {
+        return Ok(None);
+    }
+    let count = print_file_stats(needle, file, &target.name, show_names, colors, args, out)?;
+    Ok(Some(count))
 }
 
-fn process_dir(needle: &Regex, dir: &str, file_count: usize, show_counts: bool) -> Result<()> {
-    for entry in WalkDir::new(dir) {
-        let entry = entry?;
-        let name = entry.path().display().to_string();
-        // file_count += 1;
-        if !entry.file_type().is_dir() {
-            // println!("Stats for {}", name);
-            print_file_stats(needle, &name, 10, show_counts)?;
+/// Searches a single file named on the command line, printing its output
+/// straight to stdout as it goes.
+fn search_one(
+    needle: &Needle,
+    target: &Target,
+    show_names: bool,
+    colors: Option<&Colors>,
+    args: &Args,
+) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    let count =
+        search_file(needle, target, show_names, colors, args, &mut stdout).unwrap_or_else(|e| {
+            eprintln!("{}: {e}", target.name);
+            None
+        });
+    if let Some(mut report) = args.report() {
+        if let Some(count) = count {
+            let file = Some(label(&target.name));
+            report.record(&mut stdout, &Record { file, count })?;
         }
+        report.finish(
+            &mut stdout,
+            &Record {
+                file: None,
+                count: count.unwrap_or(0),
+            },
+        )?;
     }
     Ok(())
 }
 
+/// Writes one file's output, separating it from the previous file's output
+/// with `--` when context lines are requested.
+fn print_file(
+    buf: &[u8],
+    colors: Option<&Colors>,
+    args: &Args,
+    printed: &mut bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    if buf.is_empty() {
+        return Ok(());
+    }
+    let (before, after) = args.context_lines();
+    if *printed && args.prints_lines() && (before > 0 || after > 0) {
+        write_group_separator(out, colors)?;
+    }
+    out.write_all(buf)?;
+    *printed = true;
+    Ok(())
+}
+
+/// Lists the files under `dir` in path order. Hidden entries, `.git` and
+/// anything excluded by `.gitignore` or `.ignore` files are skipped unless
+/// `--hidden` or `--no-ignore` say otherwise.
+fn walk_dir(dir: &str, args: &Args) -> Vec<String> {
+    let files = Mutex::new(Vec::new());
+    WalkBuilder::new(dir)
+        .standard_filters(!args.no_ignore)
+        .hidden(!args.hidden)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build_parallel()
+        .run(|| {
+            let files = &files;
+            Box::new(move |entry| {
+                match entry {
+                    Ok(entry) if entry.file_type().is_some_and(|t| !t.is_dir()) => {
+                        files.lock().unwrap().push(entry.into_path());
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("{e}"),
+                }
+                WalkState::Continue
+            })
+        });
+    let mut files = files.into_inner().unwrap();
+    files.sort();
+    files
+        .into_iter()
+        .map(|path| path.display().to_string())
+        .collect()
+}
+
+/// Searches `targets` on a pool of threads, printing each file's output in
+/// the order given regardless of which thread finishes first. Counts go to
+/// the JSON report instead when one is asked for.
+fn search_all(
+    needle: &Needle,
+    targets: &[Target],
+    show_names: bool,
+    colors: Option<&Colors>,
+    args: &Args,
+) -> Result<()> {
+    let threads = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(targets.len());
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let tx = tx.clone();
+            let next = &next;
+            scope.spawn(move || {
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(target) = targets.get(idx) else {
+                        break;
+                    };
+                    let mut output = Vec::new();
+                    let result = search_file(needle, target, show_names, colors, args, &mut output)
+                        .map(|count| Searched { output, count });
+                    // The receiver only hangs up when printing has failed
+                    if tx.send((idx, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let mut stdout = io::stdout().lock();
+        let mut report = args.report();
+        let mut total = 0;
+        let mut printed = false;
+        let mut finished = BTreeMap::new();
+        let mut wanted = 0;
+        for (idx, result) in rx {
+            finished.insert(idx, result);
+            while let Some(result) = finished.remove(&wanted) {
+                match result {
+                    Ok(searched) => {
+                        print_file(&searched.output, colors, args, &mut printed, &mut stdout)?;
+                        if let (Some(report), Some(count)) = (&mut report, searched.count) {
+                            let file = Some(label(&targets[wanted].name));
+                            report.record(&mut stdout, &Record { file, count })?;
+                            total += count;
+                        }
+                    }
+                    Err(e) => eprintln!("{}: {e}", targets[wanted].name),
+                }
+                wanted += 1;
+            }
+        }
+        if let Some(report) = &report {
+            report.finish(
+                &mut stdout,
+                &Record {
+                    file: None,
+                    count: total,
+                },
+            )?;
+        }
+        Ok(())
+    })
+}
+
 fn run(args: Args) -> Result<()> {
-    let needle = RegexBuilder::new(&args.pattern)
+    let pattern = if args.line_regexp {
+        format!("^(?:{})$", args.pattern)
+    } else {
+        args.pattern.clone()
+    };
+    let regex = RegexBuilder::new(&pattern)
         .case_insensitive(args.ignore_case)
-        .build()?;
-
-    let file_count = args.files.iter().count();
-    // let mut files_to_process = Vec::from_iter(args.files);
+        .build()
+        .map_err(|_| anyhow!(r#"Invalid pattern "{}""#, args.pattern))?;
+    let needle = Needle {
+        regex,
+        word: args.word_regexp && !args.line_regexp,
+    };
 
-    for file_name in args.files.iter() {
-        if file_name == "-" {
-            print_file_stats(&needle, &file_name, file_count, args.counts)?;
-        } else {
-            let meta = std::fs::metadata(file_name)?;
-            if meta.is_dir() {
-                eprintln!("{file_name} is a directory");
-                process_dir(&needle, &file_name, file_count, args.counts)?;
-            } else {
-                print_file_stats(&needle, &file_name, file_count, args.counts)?;
+    let mut targets = Vec::new();
+    for file_name in &args.files {
+        match fs::metadata(file_name) {
+            Ok(meta) if meta.is_dir() => {
+                if args.recursive {
+                    targets.extend(
+                        walk_dir(file_name, &args)
+                            .into_iter()
+                            .map(|name| Target { name, walked: true }),
+                    );
+                } else {
+                    eprintln!("{file_name} is a directory");
+                }
             }
+            // Missing or unreadable files are reported when searched
+            _ => targets.push(Target {
+                name: file_name.clone(),
+                walked: false,
+            }),
         }
     }
-    Ok(())
+
+    let show_names = args.files.len() > 1 || targets.iter().any(|target| target.walked);
+    let colors = args.colors();
+    match targets.as_slice() {
+        [target] if !target.walked => {
+            search_one(&needle, target, show_names, colors.as_ref(), &args)
+        }
+        _ => search_all(&needle, &targets, show_names, colors.as_ref(), &args),
+    }
 }
 
 fn main() {
     if let Err(error) = run(Args::parse()) {
         eprintln!("{error}");
-        return std::process::exit(1);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Colors;
+
+    #[test]
+    fn test_colors_parse() {
+        assert_eq!(Colors::parse(""), Colors::default());
+
+        let colors = Colors::parse("mt=4:fn=1:ln=:ne:xx=5");
+        assert_eq!(colors.selected_match, "4");
+        assert_eq!(colors.context_match, "4");
+        assert_eq!(colors.file_name, "1");
+        assert_eq!(colors.line_number, "");
+        assert_eq!(colors.separator, "36");
+        assert!(!colors.erase);
+
+        // Parsing stops at a malformed sequence
+        let colors = Colors::parse("fn=1:ms=\x1b[5:ln=33");
+        assert_eq!(colors.file_name, "1");
+        assert_eq!(colors.selected_match, "01;31");
+        assert_eq!(colors.line_number, "32");
+    }
+
+    #[test]
+    fn test_colors_paint() {
+        let mut out = Vec::new();
+        let colors = Colors::default();
+        colors.paint(&mut out, "35", b"name").unwrap();
+        colors.paint(&mut out, "", b":").unwrap();
+        assert_eq!(out, b"\x1b[35m\x1b[Kname\x1b[m\x1b[K:");
+
+        let mut out = Vec::new();
+        Colors::parse("ne").paint(&mut out, "1", b"x").unwrap();
+        assert_eq!(out, b"\x1b[1mx\x1b[m");
     }
 }
@@ -5,6 +5,7 @@ use pretty_assertions::assert_eq;
 use rand::{Rng, distr::Alphanumeric};
 use std::{fs, path::Path};
 use sys_info::os_type;
+use tempfile::TempDir;
 
 const PRG: &str = "grepr";
 const BUSTLE: &str = "tests/inputs/bustle.txt";
@@ -226,6 +227,297 @@ fn insensitive_count_multiple() -> Result<()> {
     )
 }
 
+// --------------------------------------------------
+#[test]
+fn bustle_after_context() -> Result<()> {
+    run(
+        &["-A", "1", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.capitalized.A1",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_before_context() -> Result<()> {
+    run(
+        &["--before-context", "1", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.capitalized.B1",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_context_overridden() -> Result<()> {
+    run(
+        &["-i", "-C", "5", "-A", "2", "-B", "0", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.insensitive.A2",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_context() -> Result<()> {
+    run(
+        &["-C1", "the", BUSTLE, FOX],
+        "tests/expected/all.the.lowercase.C1",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn line_numbers() -> Result<()> {
+    run(&["-n", "the", BUSTLE], "tests/expected/bustle.txt.the.n")
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_line_numbers_byte_offsets() -> Result<()> {
+    run(
+        &["-nbi", "the", BUSTLE, FOX, NOBODY, EMPTY],
+        "tests/expected/all.the.nb.insensitive",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn files_with_matches() -> Result<()> {
+    run(
+        &["-l", "the", BUSTLE, FOX, NOBODY, EMPTY],
+        "tests/expected/all.the.l",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn files_without_match() -> Result<()> {
+    run(
+        &["--files-without-match", "the", BUSTLE, FOX, NOBODY, EMPTY],
+        "tests/expected/all.the.L",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn invert_match() -> Result<()> {
+    run(&["-v", "the", BUSTLE], "tests/expected/bustle.txt.the.v")
+}
+
+// --------------------------------------------------
+#[test]
+fn invert_match_count() -> Result<()> {
+    run(
+        &["-vc", "the", BUSTLE, FOX, NOBODY, EMPTY],
+        "tests/expected/all.the.v.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn only_matching_byte_offsets() -> Result<()> {
+    run(
+        &["-o", "-b", "-i", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.ob.insensitive",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn word_regexp() -> Result<()> {
+    run(&["-w", "in", BUSTLE], "tests/expected/bustle.txt.in.w")
+}
+
+// --------------------------------------------------
+#[test]
+fn word_regexp_non_word_pattern() -> Result<()> {
+    // The pattern may start with a non-word character, and a match next to a
+    // word character is skipped for a later one
+    Command::cargo_bin(PRG)?
+        .args(["-w", "@x"])
+        .write_stdin("a @x\nb@x\nc@xd @x\n")
+        .assert()
+        .success()
+        .stdout("a @x\nc@xd @x\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn line_regexp() -> Result<()> {
+    run(
+        &["-x", "Until eternity.", BUSTLE],
+        "tests/expected/bustle.txt.until.x",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count() -> Result<()> {
+    run(
+        &["-m", "1", "-i", "the", BUSTLE, FOX],
+        "tests/expected/all.the.m1.insensitive",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count_trailing_context() -> Result<()> {
+    run(
+        &["-m1", "-A2", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.m1.A2",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_files_with_matches() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "The"])
+        .write_stdin(fs::read_to_string(BUSTLE)?)
+        .assert()
+        .success()
+        .stdout("(standard input)\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color_always() -> Result<()> {
+    run(
+        &["--color=always", "-n", "the", BUSTLE, FOX],
+        "tests/expected/all.the.n.color",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_always_count() -> Result<()> {
+    run(
+        &["--colour=always", "-c", "the", BUSTLE, FOX],
+        "tests/expected/all.the.count.color",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_grep_colors() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/bustle.txt.until.v.C1.grep_colors")?;
+    Command::cargo_bin(PRG)?
+        .env("GREP_COLORS", "ms=4:mc=7:sl=1:cx=2:ne")
+        .args(["--color=always", "-v", "-C1", "Until", BUSTLE])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color_always_ignores_no_color() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .env("NO_COLOR", "1")
+        .args(["--color=always", "lazy", FOX])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\x1b[01;31m\x1b[Klazy\x1b[m\x1b[K",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color_auto_not_a_terminal() -> Result<()> {
+    run(
+        &["--color", "-i", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.insensitive",
+    )?;
+    run(
+        &["--color=never", "-i", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.insensitive",
+    )
+}
+
+// --------------------------------------------------
+fn gen_tree() -> Result<TempDir> {
+    let dir = TempDir::new()?;
+    let root = dir.path();
+    // An empty .git marks the tree as a repository so .gitignore applies
+    fs::create_dir_all(root.join(".git"))?;
+    fs::create_dir_all(root.join("build"))?;
+    fs::create_dir_all(root.join("src"))?;
+    fs::write(root.join(".gitignore"), "build/\n*.log\n")?;
+    fs::write(root.join(".ignore"), "notes.txt\n")?;
+    fs::write(root.join(".git/config"), "dog\n")?;
+    fs::write(root.join(".hidden.txt"), "dog\n")?;
+    fs::write(root.join("build/out.txt"), "dog\n")?;
+    fs::write(root.join("debug.log"), "dog\n")?;
+    fs::write(root.join("notes.txt"), "dog\n")?;
+    fs::write(root.join("image.bin"), "dog\0\n")?;
+    fs::write(root.join("src/a.txt"), "dog\n")?;
+    fs::write(root.join("z.txt"), "hot dog\n")?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_skips_ignored_hidden_and_binary() -> Result<()> {
+    let dir = gen_tree()?;
+    let root = dir.path().to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args(["-r", "dog", root])
+        .assert()
+        .success()
+        .stdout(format!("{root}/src/a.txt:dog\n{root}/z.txt:hot dog\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_no_ignore_hidden_text() -> Result<()> {
+    let dir = gen_tree()?;
+    let root = dir.path().to_str().unwrap();
+    let expected: String = [
+        ".hidden.txt",
+        "build/out.txt",
+        "debug.log",
+        "image.bin",
+        "notes.txt",
+        "src/a.txt",
+        "z.txt",
+    ]
+    .iter()
+    .map(|name| format!("{root}/{name}\n"))
+    .collect();
+    Command::cargo_bin(PRG)?
+        .args(["-rl", "--no-ignore", "--hidden", "--text", "dog", root])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_output_in_path_order() -> Result<()> {
+    let dir = TempDir::new()?;
+    let mut expected = String::new();
+    for i in 0..20 {
+        let sub = dir.path().join(format!("d{i:02}"));
+        fs::create_dir(&sub)?;
+        for j in 0..20 {
+            let file = sub.join(format!("f{j:02}.txt"));
+            fs::write(&file, format!("match {i} {j}\nskip\n"))?;
+            expected.push_str(&format!("{}:match {i} {j}\n", file.display()));
+        }
+    }
+    Command::cargo_bin(PRG)?
+        .args(["-r", "match", dir.path().to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn warns_dir_not_recursive() -> Result<()> {
@@ -281,3 +573,65 @@ fn stdin_insensitive_count() -> Result<()> {
     assert_eq!(stdout, expected);
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn insensitive_count_multiple_json() -> Result<()> {
+    run(
+        &["-ic", "--json", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.lowercase.insensitive.count.json",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn sensitive_count_multiple_ndjson() -> Result<()> {
+    run(
+        &["-c", "--ndjson", "The", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.capitalized.count.ndjson",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_count_ndjson_skips_binary() -> Result<()> {
+    let dir = gen_tree()?;
+    let root = dir.path().to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args(["-rc", "--ndjson", "dog", root])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{{\"file\":\"{root}/src/a.txt\",\"count\":1}}\n\
+             {{\"file\":\"{root}/z.txt\",\"count\":1}}\n\
+             {{\"total\":{{\"count\":2}}}}\n"
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_count_json() -> Result<()> {
+    // Stdin is named as it is in text output
+    Command::cargo_bin(PRG)?
+        .args(["-c", "--json", "fox", "-"])
+        .write_stdin(fs::read_to_string(FOX)?)
+        .assert()
+        .success()
+        .stdout(
+            "{\"files\":[{\"file\":\"(standard input)\",\"count\":1}],\
+             \"total\":{\"count\":1}}\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_json_without_count() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--json", "the", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--count"));
+    Ok(())
+}
@@ -0,0 +1 @@
+The bustle in a house
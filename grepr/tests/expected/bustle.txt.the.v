@@ -0,0 +1,8 @@
+The bustle in a house
+The morning after death
+Is solemnest of industries
+Enacted upon earth,—
+
+And putting love away
+We shall not want to use again
+Until eternity.
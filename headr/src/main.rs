@@ -1,53 +1,181 @@
 use anyhow::Result;
 use clap::Parser;
-use std::io::BufRead;
-use std::io::Read;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+/// How much of a file to print: the first N lines or bytes, or all but the
+/// last N.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Count {
+    First(u64),
+    AllBut(u64),
+}
 
 #[derive(Parser, Debug)]
 #[command(version, author, about)]
 struct Args {
     #[arg(value_name="FILES", default_value="-", num_args=1..)]
     files: Vec<String>,
-    #[arg(value_name="LINES",short('n'), long("lines"), default_value="10", value_parser=clap::value_parser!(u64).range(1..))]
-    lines: u64,
+    /// Print the first LINES lines, or with a leading '-' all but the last
+    #[arg(
+        value_name = "LINES",
+        short('n'),
+        long("lines"),
+        default_value = "10",
+        allow_hyphen_values = true,
+        value_parser = parse_lines
+    )]
+    lines: Count,
+    /// Print the first BYTES bytes, or with a leading '-' all but the last;
+    /// BYTES may end in a suffix such as K (1024), KB (1000), MiB or GB
     #[arg(
-        value_name="BYTES",
+        value_name = "BYTES",
         short('c'),
-        long("bytes"), value_parser=clap::value_parser!(u64).range(1..), conflicts_with("lines"))]
-    bytes: Option<u64>,
+        long("bytes"),
+        allow_hyphen_values = true,
+        value_parser = parse_bytes,
+        conflicts_with("lines")
+    )]
+    bytes: Option<Count>,
+}
+
+/// Multipliers for the suffixes `-c` takes, as GNU head does.
+const SUFFIXES: [(&str, u64); 20] = [
+    ("b", 512),
+    ("K", 1 << 10),
+    ("KiB", 1 << 10),
+    ("kB", 1000),
+    ("KB", 1000),
+    ("M", 1 << 20),
+    ("MiB", 1 << 20),
+    ("MB", 1000_u64.pow(2)),
+    ("G", 1 << 30),
+    ("GiB", 1 << 30),
+    ("GB", 1000_u64.pow(3)),
+    ("T", 1 << 40),
+    ("TiB", 1 << 40),
+    ("TB", 1000_u64.pow(4)),
+    ("P", 1 << 50),
+    ("PiB", 1 << 50),
+    ("PB", 1000_u64.pow(5)),
+    ("E", 1 << 60),
+    ("EiB", 1 << 60),
+    ("EB", 1000_u64.pow(6)),
+];
+
+fn parse_lines(s: &str) -> Result<Count, String> {
+    let (all_but, number) = match s.strip_prefix('-') {
+        Some(number) => (true, number),
+        None => (false, s),
+    };
+    let number = number.parse::<u64>().map_err(|e| e.to_string())?;
+    Ok(if all_but {
+        Count::AllBut(number)
+    } else {
+        Count::First(number)
+    })
+}
+
+fn parse_bytes(s: &str) -> Result<Count, String> {
+    // The number ends where its suffix, if any, starts
+    let end = s.trim_end_matches(char::is_alphabetic).len();
+    let multiplier = match &s[end..] {
+        "" => 1,
+        suffix => match SUFFIXES.iter().find(|(name, _)| *name == suffix) {
+            Some((_, multiplier)) => *multiplier,
+            // Report the bad suffix as a bad digit, like a plain number
+            None => return parse_lines(s),
+        },
+    };
+    match parse_lines(&s[..end])? {
+        Count::First(n) => n.checked_mul(multiplier).map(Count::First),
+        Count::AllBut(n) => n.checked_mul(multiplier).map(Count::AllBut),
+    }
+    .ok_or_else(|| "number too large to fit in target type".to_string())
+}
+
+/// Copies all but the last `count` lines of `reader` to `out`, holding back
+/// no more than `count` lines at a time.
+fn copy_lines_but(reader: impl io::BufRead, out: &mut impl Write, count: usize) -> Result<()> {
+    let mut held = VecDeque::new();
+    for line in clir::lines(reader) {
+        held.push_back(line?);
+        if held.len() > count
+            && let Some(line) = held.pop_front()
+        {
+            out.write_all(&line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies all but the last `count` bytes of `reader` to `out`, holding back
+/// no more than `count` bytes at a time.
+fn copy_bytes_but(mut reader: impl Read, out: &mut impl Write, count: usize) -> Result<()> {
+    let mut held = VecDeque::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        held.extend(&buffer[..read]);
+        if held.len() > count {
+            let excess = held.len() - count;
+            let (front, back) = held.as_slices();
+            let from_front = excess.min(front.len());
+            out.write_all(&front[..from_front])?;
+            out.write_all(&back[..excess - from_front])?;
+            held.drain(..excess);
+        }
+    }
 }
 
 fn run(args: Args) -> Result<()> {
-    let file_count = args.files.iter().count();
+    let file_count = args.files.len();
+    let mut stdout = io::stdout().lock();
+    let mut failed = false;
     for (idx, filename) in args.files.iter().enumerate() {
-        let mut file = clir::open(&filename)?;
-        let mut contents = String::new();
+        let file = match clir::open(filename) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("{filename}: {err}");
+                failed = true;
+                continue;
+            }
+        };
         if file_count > 1 {
             if idx > 0 {
-                println!()
+                writeln!(stdout)?;
             }
-            println!("==> {filename} <==")
+            writeln!(stdout, "==> {filename} <==")?;
         }
-        match args.bytes {
-            Some(byte_count) => {
-                let bytes = file
-                    .bytes()
-                    .take(byte_count as usize)
-                    .collect::<Result<Vec<_>, _>>()?;
-                print!("{}", String::from_utf8_lossy(&bytes));
+        match (args.bytes, args.lines) {
+            (Some(Count::First(byte_count)), _) => {
+                io::copy(&mut file.take(byte_count), &mut stdout)?;
+            }
+            (Some(Count::AllBut(byte_count)), _) => {
+                let byte_count = usize::try_from(byte_count).unwrap_or(usize::MAX);
+                copy_bytes_but(file, &mut stdout, byte_count)?;
             }
-            _ => {
-                for _ in 0..args.lines {
-                    let read_bytes = file.read_line(&mut contents)?;
-                    if read_bytes == 0 {
-                        break;
-                    }
-                    print!("{contents}");
-                    contents.clear();
+            (None, Count::First(line_count)) => {
+                let line_count = usize::try_from(line_count).unwrap_or(usize::MAX);
+                for line in clir::lines(file).take(line_count) {
+                    stdout.write_all(&line?)?;
                 }
             }
+            (None, Count::AllBut(line_count)) => {
+                let line_count = usize::try_from(line_count).unwrap_or(usize::MAX);
+                copy_lines_but(file, &mut stdout, line_count)?;
+            }
         }
     }
+    if failed {
+        stdout.flush()?;
+        std::process::exit(1);
+    }
     Ok(())
 }
 
@@ -57,3 +185,61 @@ fn main() {
         std::process::exit(1);
     }
 }
+
+// --------------------------------------------------
+#[cfg(test)]
+mod unit_tests {
+    use super::{Count, copy_bytes_but, copy_lines_but, parse_bytes, parse_lines};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_lines() {
+        assert_eq!(parse_lines("3"), Ok(Count::First(3)));
+        assert_eq!(parse_lines("0"), Ok(Count::First(0)));
+        assert_eq!(parse_lines("-3"), Ok(Count::AllBut(3)));
+        assert!(parse_lines("--3").is_err());
+        assert!(parse_lines("-").is_err());
+        assert!(parse_lines("3K").is_err());
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("3"), Ok(Count::First(3)));
+        assert_eq!(parse_bytes("1K"), Ok(Count::First(1024)));
+        assert_eq!(parse_bytes("2kB"), Ok(Count::First(2000)));
+        assert_eq!(parse_bytes("5M"), Ok(Count::First(5 << 20)));
+        assert_eq!(parse_bytes("2GiB"), Ok(Count::First(2 << 30)));
+        assert_eq!(parse_bytes("-2b"), Ok(Count::AllBut(1024)));
+        assert_eq!(
+            parse_bytes("3X"),
+            Err("invalid digit found in string".to_string())
+        );
+        assert_eq!(
+            parse_bytes("K"),
+            Err("cannot parse integer from empty string".to_string())
+        );
+        assert_eq!(
+            parse_bytes("20EB"),
+            Err("number too large to fit in target type".to_string())
+        );
+    }
+
+    #[test]
+    fn test_copy_but() {
+        let mut out = vec![];
+        copy_lines_but(&b"a\nb\nc"[..], &mut out, 2).unwrap();
+        assert_eq!(out, b"a\n");
+
+        let mut out = vec![];
+        copy_lines_but(&b"a\nb\nc\n"[..], &mut out, 0).unwrap();
+        assert_eq!(out, b"a\nb\nc\n");
+
+        let mut out = vec![];
+        copy_bytes_but(&b"abcdef"[..], &mut out, 4).unwrap();
+        assert_eq!(out, b"ab");
+
+        let mut out = vec![];
+        copy_bytes_but(&b"abc"[..], &mut out, 4).unwrap();
+        assert_eq!(out, b"");
+    }
+}
@@ -91,6 +91,8 @@ fn skips_bad_file() -> Result<()> {
     Command::cargo_bin(PRG)?
         .args([EMPTY, &bad, ONE])
         .assert()
+        .failure()
+        .stdout(predicate::str::contains("==> ./tests/inputs/one.txt <=="))
         .stderr(predicate::str::is_match(expected)?);
 
     Ok(())
@@ -420,3 +422,52 @@ fn multiple_files_c4() -> Result<()> {
         "tests/expected/all.c4.out",
     )
 }
+
+#[test]
+fn twelve_n_minus_3() -> Result<()> {
+    run(&[TWELVE, "-n", "-3"], "tests/expected/twelve.txt.n-3.out")
+}
+
+#[test]
+fn twelve_n_minus_3_stdin() -> Result<()> {
+    run_stdin(&["-n", "-3"], TWELVE, "tests/expected/twelve.txt.n-3.out")
+}
+
+#[test]
+fn twelve_c_minus_5() -> Result<()> {
+    run(&[TWELVE, "-c", "-5"], "tests/expected/twelve.txt.c-5.out")
+}
+
+#[test]
+fn twelve_c_minus_1k() -> Result<()> {
+    run(&[TWELVE, "-c", "-1K"], "tests/expected/twelve.txt.c-1K.out")
+}
+
+#[test]
+fn twelve_c1k() -> Result<()> {
+    run(
+        &[TWELVE, "--bytes", "1K"],
+        "tests/expected/twelve.txt.c1K.out",
+    )
+}
+
+#[test]
+fn multiple_files_n_minus_2() -> Result<()> {
+    run(
+        &["-n", "-2", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.n-2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_bytes_suffix() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "2Q", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '2Q' for '--bytes <BYTES>': invalid digit found in string",
+        ));
+    Ok(())
+}
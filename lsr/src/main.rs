@@ -1,6 +1,9 @@
 use anyhow::Result;
+use chrono::{DateTime, Local};
 use clap::Parser;
-use std::{fs, fs::read_dir, path::PathBuf};
+use std::{fs, os::unix::fs::MetadataExt, path::PathBuf};
+use tabular::{Row, Table};
+use users::{get_group_by_gid, get_user_by_uid};
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -44,10 +47,94 @@ fn find_files(paths: &[String], show_hidden: bool) -> Result<Vec<PathBuf>> {
     Ok(found_paths)
 }
 
+#[derive(Clone, Copy)]
+enum Owner {
+    User,
+    Group,
+    Other,
+}
+
+impl Owner {
+    fn masks(&self) -> [u32; 3] {
+        match self {
+            Self::User => [0o400, 0o200, 0o100],
+            Self::Group => [0o040, 0o020, 0o010],
+            Self::Other => [0o004, 0o002, 0o001],
+        }
+    }
+}
+
+fn mk_triple(mode: u32, owner: Owner) -> String {
+    let [read, write, execute] = owner.masks();
+    format!(
+        "{}{}{}",
+        if mode & read == 0 { "-" } else { "r" },
+        if mode & write == 0 { "-" } else { "w" },
+        if mode & execute == 0 { "-" } else { "x" },
+    )
+}
+
+fn format_mode(mode: u32) -> String {
+    format!(
+        "{}{}{}",
+        mk_triple(mode, Owner::User),
+        mk_triple(mode, Owner::Group),
+        mk_triple(mode, Owner::Other),
+    )
+}
+
+fn format_output(paths: &[PathBuf]) -> Result<String> {
+    //         1   2     3     4     5     6     7     8
+    let fmt = "{:<}{:<}  {:>}  {:<}  {:<}  {:>}  {:<}  {:<}";
+    let mut table = Table::new(fmt);
+
+    for path in paths {
+        let metadata = path.symlink_metadata()?;
+
+        let file_type = if metadata.is_dir() {
+            "d"
+        } else if metadata.is_symlink() {
+            "l"
+        } else {
+            "-"
+        };
+
+        let uid = metadata.uid();
+        let user = get_user_by_uid(uid)
+            .map(|u| u.name().to_string_lossy().into_owned())
+            .unwrap_or_else(|| uid.to_string());
+
+        let gid = metadata.gid();
+        let group = get_group_by_gid(gid)
+            .map(|g| g.name().to_string_lossy().into_owned())
+            .unwrap_or_else(|| gid.to_string());
+
+        let modified: DateTime<Local> = DateTime::from(metadata.modified()?);
+
+        table.add_row(
+            Row::new()
+                .with_cell(file_type)
+                .with_cell(format_mode(metadata.mode()))
+                .with_cell(metadata.nlink())
+                .with_cell(user)
+                .with_cell(group)
+                .with_cell(metadata.len())
+                .with_cell(modified.format("%b %d %y %H:%M"))
+                .with_cell(path.display()),
+        );
+    }
+
+    Ok(format!("{table}"))
+}
+
 fn run(args: Args) -> Result<()> {
     let files = find_files(&args.paths, args.show_hidden)?;
-    for file in files {
-        println!("{}", file.file_name().unwrap().to_string_lossy());
+    if args.long_format {
+        print!("{}", format_output(&files)?);
+    } else {
+        for file in files {
+            println!("{}", file.file_name().unwrap().to_string_lossy());
+        }
     }
     Ok(())
 }
@@ -58,3 +145,24 @@ fn main() {
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Owner, format_mode, mk_triple};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_mk_triple() {
+        assert_eq!(mk_triple(0o751, Owner::User), "rwx");
+        assert_eq!(mk_triple(0o751, Owner::Group), "r-x");
+        assert_eq!(mk_triple(0o751, Owner::Other), "--x");
+        assert_eq!(mk_triple(0o600, Owner::Other), "---");
+    }
+
+    #[test]
+    fn test_format_mode() {
+        assert_eq!(format_mode(0o755), "rwxr-xr-x");
+        assert_eq!(format_mode(0o421), "r---w---x");
+        assert_eq!(format_mode(0o100644), "rw-r--r--");
+    }
+}
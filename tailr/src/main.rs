@@ -1,7 +1,11 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use clap::Parser;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use clir::Compression;
+use std::collections::VecDeque;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::thread;
+use std::time::Duration;
 
 #[derive(Parser)]
 struct Args {
@@ -13,6 +17,17 @@ struct Args {
     bytes: Option<String>,
     #[arg(short, long)]
     quiet: bool,
+    #[arg(short('f'), long)]
+    follow: bool,
+    #[arg(short('F'))]
+    follow_name: bool,
+    #[arg(
+        short('s'),
+        long("sleep-interval"),
+        value_name = "SECONDS",
+        default_value = "1.0"
+    )]
+    sleep_interval: f64,
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -41,23 +56,265 @@ fn parse_quantity(qty: String) -> Result<Action> {
     })
 }
 
-fn counts(file_name: &str) -> Result<(i64, i64)> {
-    let mut line = String::new();
-    let mut line_count: i64 = 0;
-    let mut byte_count: i64 = 0;
-    let mut file = clir::open(&file_name)?;
-    loop {
-        let bc = file.read_line(&mut line)? as i64;
-        if bc == 0 {
-            break;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy)]
+enum Unit {
+    Lines,
+    Bytes,
+}
+
+enum Input {
+    File(File),
+    Stream(Box<dyn BufRead>),
+}
+
+impl Input {
+    /// Regular files are read from the end; stdin, pipes, devices and
+    /// compressed files can only be streamed.
+    fn open(file_name: &str) -> Result<Self> {
+        if file_name == "-" || !fs::metadata(file_name)?.is_file() {
+            return Ok(Input::Stream(clir::open(file_name)?));
+        }
+        let mut file = File::open(file_name)?;
+        let mut header = Vec::new();
+        (&mut file).take(8).read_to_end(&mut header)?;
+        if Compression::detect(&header).is_some() {
+            return Ok(Input::Stream(clir::open(file_name)?));
+        }
+        file.rewind()?;
+        Ok(Input::File(file))
+    }
+}
+
+/// Finds the offset of the first of the last `count` lines by reading
+/// backwards from the end in `chunk_size` blocks.
+fn last_lines_start<R: Read + Seek>(reader: &mut R, count: u64, chunk_size: usize) -> Result<u64> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let mut buf = vec![0; chunk_size];
+    let mut pos = len;
+    let mut found = 0;
+    while pos > 0 {
+        let size = chunk_size.min(pos as usize);
+        pos -= size as u64;
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut buf[..size])?;
+        for (i, &byte) in buf[..size].iter().enumerate().rev() {
+            let next = pos + i as u64 + 1;
+            // The final newline terminates the last line rather than starting one
+            if byte == b'\n' && next != len {
+                found += 1;
+                if found == count {
+                    return Ok(next);
+                }
+            }
+        }
+    }
+    Ok(0)
+}
+
+fn tail_stream(
+    mut reader: impl BufRead,
+    action: &Action,
+    unit: Unit,
+    out: &mut impl Write,
+) -> Result<()> {
+    match (action, unit) {
+        (Action::Everything, _) => {
+            io::copy(&mut reader, out)?;
+        }
+        (Action::From(0), _) => {}
+        (Action::From(start), Unit::Lines) if *start > 0 => {
+            for line in clir::lines(reader).skip(*start as usize - 1) {
+                out.write_all(&line?)?;
+            }
+        }
+        (Action::From(start), Unit::Bytes) if *start > 0 => {
+            io::copy(
+                &mut reader.by_ref().take(*start as u64 - 1),
+                &mut io::sink(),
+            )?;
+            io::copy(&mut reader, out)?;
+        }
+        (Action::From(count), Unit::Lines) => {
+            let keep = count.unsigned_abs() as usize;
+            let mut ring = VecDeque::new();
+            for line in clir::lines(reader) {
+                if ring.len() == keep {
+                    ring.pop_front();
+                }
+                ring.push_back(line?);
+            }
+            for line in ring {
+                out.write_all(&line)?;
+            }
+        }
+        (Action::From(count), Unit::Bytes) => {
+            let keep = count.unsigned_abs() as usize;
+            let mut ring = VecDeque::new();
+            let mut buf = vec![0; CHUNK_SIZE];
+            loop {
+                let read = reader.read(&mut buf)?;
+                if read == 0 {
+                    break;
+                }
+                ring.extend(&buf[..read]);
+                if ring.len() > keep {
+                    ring.drain(..ring.len() - keep);
+                }
+            }
+            let (front, back) = ring.as_slices();
+            out.write_all(front)?;
+            out.write_all(back)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the requested tail of a regular file, leaving it positioned at the
+/// end of what was printed.
+fn tail_file(file: &mut File, action: &Action, unit: Unit, out: &mut impl Write) -> Result<()> {
+    let start = match (action, unit) {
+        (Action::From(0), _) => {
+            file.seek(SeekFrom::End(0))?;
+            return Ok(());
+        }
+        (Action::From(count), Unit::Lines) if *count < 0 => {
+            last_lines_start(file, count.unsigned_abs(), CHUNK_SIZE)?
+        }
+        (Action::From(count), Unit::Bytes) if *count < 0 => {
+            let len = file.seek(SeekFrom::End(0))?;
+            len.saturating_sub(count.unsigned_abs())
+        }
+        (Action::From(start), Unit::Bytes) => {
+            let len = file.seek(SeekFrom::End(0))?;
+            len.min(*start as u64 - 1)
+        }
+        _ => return tail_stream(BufReader::new(file), action, unit, out),
+    };
+    file.seek(SeekFrom::Start(start))?;
+    io::copy(file, out)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+struct Followed {
+    name: String,
+    file: Option<File>,
+    id: Option<(u64, u64)>,
+    pos: u64,
+}
+
+impl Followed {
+    fn new(name: &str, mut file: File) -> Result<Self> {
+        let pos = file.stream_position()?;
+        let id = file_id(&file.metadata()?);
+        Ok(Followed {
+            name: name.to_string(),
+            file: Some(file),
+            id,
+            pos,
+        })
+    }
+
+    /// A file that can't be opened yet, read from the start once it appears.
+    fn missing(name: &str) -> Self {
+        Followed {
+            name: name.to_string(),
+            file: None,
+            id: None,
+            pos: 0,
+        }
+    }
+
+    /// Copies whatever was appended since the last poll, returning the number
+    /// of bytes written. A file that shrank is assumed to have been truncated
+    /// and is read again from the start.
+    fn read_new(&mut self, out: &mut impl Write) -> Result<u64> {
+        let Some(file) = &mut self.file else {
+            return Ok(0);
+        };
+        let len = file.metadata()?.len();
+        if len < self.pos {
+            eprintln!("tailr: {}: file truncated", self.name);
+            self.pos = 0;
+        }
+        if len == self.pos {
+            return Ok(0);
         }
-        line_count += 1;
-        byte_count += bc;
+        file.seek(SeekFrom::Start(self.pos))?;
+        let copied = io::copy(&mut file.take(len - self.pos), out)?;
+        self.pos += copied;
+        Ok(copied)
+    }
 
-        line.clear();
+    /// Reopens the file when the name now points somewhere else, as happens
+    /// with rename-based log rotation.
+    fn reopen_if_rotated(&mut self) {
+        match fs::metadata(&self.name) {
+            Ok(meta) if self.file.is_none() || file_id(&meta) != self.id => {
+                if let Ok(file) = File::open(&self.name) {
+                    if self.file.is_none() {
+                        eprintln!("tailr: '{}' has appeared; following new file", self.name);
+                    } else {
+                        eprintln!(
+                            "tailr: '{}' has been replaced; following new file",
+                            self.name
+                        );
+                    }
+                    self.file = Some(file);
+                    self.id = file_id(&meta);
+                    self.pos = 0;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                if self.file.take().is_some() {
+                    eprintln!("tailr: '{}' has become inaccessible: {e}", self.name);
+                }
+            }
+        }
     }
+}
 
-    return Ok((line_count, byte_count));
+fn follow(
+    mut files: Vec<Followed>,
+    by_name: bool,
+    interval: Duration,
+    headers: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut last = files.len().checked_sub(1);
+    loop {
+        for (idx, followed) in files.iter_mut().enumerate() {
+            let mut buf = Vec::new();
+            followed.read_new(&mut buf)?;
+            if by_name {
+                followed.reopen_if_rotated();
+                followed.read_new(&mut buf)?;
+            }
+            if buf.is_empty() {
+                continue;
+            }
+            if headers && last != Some(idx) {
+                writeln!(out, "\n==> {} <==", followed.name)?;
+                last = Some(idx);
+            }
+            out.write_all(&buf)?;
+            out.flush()?;
+        }
+        thread::sleep(interval);
+    }
 }
 
 fn run(args: Args) -> Result<()> {
@@ -70,88 +327,59 @@ fn run(args: Args) -> Result<()> {
         .transpose()
         .map_err(|e| anyhow!("illegal line count -- {e}"))?;
 
-    for (idx, file_name) in args.files.iter().enumerate() {
-        let mut file = clir::open(&file_name)?;
-        let (line_count, _byte_count) = counts(&file_name)?;
-        if multiple_files > 1 && !args.quiet {
-            if idx > 0 {
-                println!()
+    if !(args.sleep_interval >= 0.0 && args.sleep_interval.is_finite()) {
+        bail!("invalid number of seconds: '{}'", args.sleep_interval);
+    }
+    let following = args.follow || args.follow_name;
+    let mut followed = Vec::new();
+
+    let mut stdout = io::stdout().lock();
+    let (action, unit) = match bytes {
+        Some(action) => (action, Unit::Bytes),
+        None => (lines, Unit::Lines),
+    };
+
+    let mut headed = false;
+    for file_name in &args.files {
+        let input = match Input::open(file_name) {
+            Ok(input) => input,
+            // With -F, a file that isn't there yet is followed until it is
+            Err(e) if args.follow_name => {
+                eprintln!("tailr: cannot open '{file_name}' for reading: {e}");
+                followed.push(Followed::missing(file_name));
+                continue;
             }
-            println!("==> {} <==", file_name);
-        }
-        if let Some(bt) = &bytes {
-            match bt {
-                Action::Everything => {
-                    let mut l = String::new();
-                    loop {
-                        let b = file.read_line(&mut l)?;
-                        if b == 0 {
-                            break;
-                        }
-                        print!("{}", l);
-                        l.clear();
-                    }
-                }
-                Action::From(count) => {
-                    if *count != 0 {
-                        let skip_until = if *count < 0 {
-                            let negative = _byte_count + count;
-                            if negative < 0 { 0 } else { negative }
-                        } else {
-                            if *count > _byte_count { 0 } else { count - 1 }
-                        };
-                        let mut ff = BufReader::new(File::open(&file_name)?);
-                        ff.seek(SeekFrom::Start(skip_until as u64))?;
-                        let mut bbytes = Vec::new();
-                        ff.read_to_end(&mut bbytes)?;
-
-                        let srtr = String::from_utf8_lossy(&bbytes);
-                        if !bbytes.is_empty() {
-                            print!("{}", srtr);
-                        }
-                    }
-                }
+            Err(e) => return Err(e),
+        };
+        if multiple_files > 1 && !args.quiet {
+            if headed {
+                writeln!(stdout)?;
             }
-        } else {
-            match lines {
-                Action::Everything => {
-                    let mut l = String::new();
-                    loop {
-                        let b = file.read_line(&mut l)?;
-                        if b == 0 {
-                            break;
-                        }
-                        print!("{}", l);
-                        l.clear();
-                    }
-                }
-                Action::From(count) => {
-                    if count != 0 && line_count >= count {
-                        let skip_until = if count < 0 {
-                            let negative = line_count + count;
-                            if negative < 0 { 0 } else { negative }
-                        } else {
-                            if count > line_count { 0 } else { count - 1 }
-                        };
-                        let mut idx = 0;
-                        let mut l = String::new();
-                        loop {
-                            let b = file.read_line(&mut l)?;
-                            if b == 0 {
-                                break;
-                            }
-                            if idx >= skip_until {
-                                print!("{}", l);
-                            }
-                            idx += 1;
-                            l.clear();
-                        }
-                    }
+            writeln!(stdout, "==> {} <==", file_name)?;
+            headed = true;
+        }
+        match input {
+            Input::File(mut file) => {
+                tail_file(&mut file, &action, unit, &mut stdout)?;
+                if following {
+                    followed.push(Followed::new(file_name, file)?);
                 }
             }
+            Input::Stream(reader) => tail_stream(reader, &action, unit, &mut stdout)?,
         }
     }
 
+    if !followed.is_empty() {
+        stdout.flush()?;
+        follow(
+            followed,
+            args.follow_name,
+            Duration::from_secs_f64(args.sleep_interval),
+            multiple_files > 1 && !args.quiet,
+            &mut stdout,
+        )?;
+    }
+
     Ok(())
 }
 
@@ -166,8 +394,10 @@ fn main() {
 mod tests {
     use super::Action;
     use super::parse_quantity;
+    use super::{Unit, last_lines_start, tail_stream};
     use anyhow::Result;
     use pretty_assertions::assert_eq;
+    use std::io::Cursor;
 
     fn test_positive() -> Result<()> {
         let parsed = parse_quantity("10".to_string())?;
@@ -186,4 +416,52 @@ mod tests {
         assert_eq!(parsed, Action::Everything);
         Ok(())
     }
+
+    #[test]
+    fn test_last_lines_start() -> Result<()> {
+        let text = b"one\ntwo\nthree\n";
+        for chunk_size in [1, 2, 3, 5, 64] {
+            let mut cursor = Cursor::new(text);
+            assert_eq!(last_lines_start(&mut cursor, 1, chunk_size)?, 8);
+            assert_eq!(last_lines_start(&mut cursor, 2, chunk_size)?, 4);
+            assert_eq!(last_lines_start(&mut cursor, 3, chunk_size)?, 0);
+            assert_eq!(last_lines_start(&mut cursor, 10, chunk_size)?, 0);
+        }
+
+        // Without a trailing newline the partial line counts as the last one
+        let mut cursor = Cursor::new(b"one\ntwo");
+        assert_eq!(last_lines_start(&mut cursor, 1, 2)?, 4);
+
+        let mut cursor = Cursor::new(b"\n\n\n");
+        assert_eq!(last_lines_start(&mut cursor, 2, 2)?, 1);
+
+        let mut cursor = Cursor::new(b"");
+        assert_eq!(last_lines_start(&mut cursor, 3, 2)?, 0);
+        Ok(())
+    }
+
+    fn streamed(input: &[u8], action: Action, unit: Unit) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        tail_stream(Cursor::new(input), &action, unit, &mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_tail_stream() -> Result<()> {
+        let text = b"one\ntwo\nthree";
+        assert_eq!(
+            streamed(text, Action::From(-2), Unit::Lines)?,
+            b"two\nthree"
+        );
+        assert_eq!(streamed(text, Action::From(-9), Unit::Lines)?, text);
+        assert_eq!(streamed(text, Action::From(2), Unit::Lines)?, b"two\nthree");
+        assert_eq!(streamed(text, Action::From(4), Unit::Lines)?, b"");
+        assert_eq!(streamed(text, Action::From(-3), Unit::Bytes)?, b"ree");
+        assert_eq!(streamed(text, Action::From(-99), Unit::Bytes)?, text);
+        assert_eq!(streamed(text, Action::From(11), Unit::Bytes)?, b"ree");
+        assert_eq!(streamed(text, Action::From(99), Unit::Bytes)?, b"");
+        assert_eq!(streamed(text, Action::From(0), Unit::Bytes)?, b"");
+        assert_eq!(streamed(text, Action::Everything, Unit::Lines)?, text);
+        Ok(())
+    }
 }
@@ -3,8 +3,14 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{Rng, distr::Alphanumeric};
-use std::fs::{self, File};
-use std::io::Read;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
 
 const PRG: &str = "tailr";
 const EMPTY: &str = "tests/inputs/empty.txt";
@@ -114,6 +120,23 @@ fn run(args: &[&str], expected_file: &str) -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_stdin(args: &[&str], input_file: &str, expected_file: &str) -> Result<()> {
+    let expected = fs::read(expected_file)?;
+    let output = Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(fs::read(input_file)?)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&expected)
+    );
+
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty() -> Result<()> {
@@ -828,3 +851,246 @@ fn multiple_files_c_plus_3() -> Result<()> {
         "tests/expected/all.c+3.out",
     )
 }
+
+// --------------------------------------------------
+/// How long to wait for a follower's output before giving up.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A running `tailr -f`, whose output is collected as it arrives.
+struct Follower {
+    child: Child,
+    stdout: Arc<Mutex<Vec<u8>>>,
+    reader: thread::JoinHandle<()>,
+}
+
+impl Follower {
+    fn spawn(args: &[&str]) -> Result<Self> {
+        let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+            .args(["-s", "0.05"])
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut pipe = child.stdout.take().unwrap();
+        let stdout = Arc::new(Mutex::new(Vec::new()));
+        let collected = Arc::clone(&stdout);
+        let reader = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            while let Ok(read @ 1..) = pipe.read(&mut buf) {
+                collected.lock().unwrap().extend(&buf[..read]);
+            }
+        });
+        Ok(Follower {
+            child,
+            stdout,
+            reader,
+        })
+    }
+
+    /// Waits until everything printed so far is `expected`, failing if it
+    /// isn't before the timeout.
+    fn wait_for(&self, expected: &str) {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let stdout = String::from_utf8_lossy(&self.stdout.lock().unwrap()).into_owned();
+            if stdout == expected {
+                return;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "timed out waiting for {expected:?}, got {stdout:?}"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn stop(mut self) -> Result<(String, String)> {
+        self.child.kill()?;
+        let output = self.child.wait_with_output()?;
+        self.reader.join().unwrap();
+        let stdout = self.stdout.lock().unwrap().clone();
+        Ok((
+            String::from_utf8(stdout)?,
+            String::from_utf8(output.stderr)?,
+        ))
+    }
+}
+
+fn append(path: &Path, text: &str) -> Result<()> {
+    let mut file = OpenOptions::new().append(true).open(path)?;
+    file.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+#[test]
+fn follow_appended() -> Result<()> {
+    let dir = TempDir::new()?;
+    let log = dir.path().join("app.log");
+    fs::write(&log, "one\ntwo\n")?;
+
+    let follower = Follower::spawn(&["-f", "-n", "1", log.to_str().unwrap()])?;
+    follower.wait_for("two\n");
+    append(&log, "three\n")?;
+    follower.wait_for("two\nthree\n");
+    append(&log, "four\n")?;
+    follower.wait_for("two\nthree\nfour\n");
+    follower.stop()?;
+    Ok(())
+}
+
+#[test]
+fn follow_truncated() -> Result<()> {
+    let dir = TempDir::new()?;
+    let log = dir.path().join("app.log");
+    fs::write(&log, "one\ntwo\n")?;
+
+    let follower = Follower::spawn(&["-f", log.to_str().unwrap()])?;
+    follower.wait_for("one\ntwo\n");
+    fs::write(&log, "new\n")?;
+    follower.wait_for("one\ntwo\nnew\n");
+    let (_, stderr) = follower.stop()?;
+
+    assert!(stderr.contains("file truncated"));
+    Ok(())
+}
+
+#[test]
+fn follow_name_rotated() -> Result<()> {
+    let dir = TempDir::new()?;
+    let log = dir.path().join("app.log");
+    fs::write(&log, "one\n")?;
+
+    let follower = Follower::spawn(&["-F", log.to_str().unwrap()])?;
+    follower.wait_for("one\n");
+    append(&log, "two\n")?;
+    fs::rename(&log, dir.path().join("app.log.1"))?;
+    fs::write(&log, "three\n")?;
+    follower.wait_for("one\ntwo\nthree\n");
+    let (_, stderr) = follower.stop()?;
+
+    assert!(stderr.contains("following new file"));
+    Ok(())
+}
+
+#[test]
+fn follow_name_missing() -> Result<()> {
+    let dir = TempDir::new()?;
+    let missing = dir.path().join("missing.log");
+    let present = dir.path().join("present.log");
+    fs::write(&present, "one\n")?;
+    let (missing_name, present_name) = (missing.to_str().unwrap(), present.to_str().unwrap());
+
+    // The missing file comes first, so it was looked for by the time the
+    // present one is printed
+    let follower = Follower::spawn(&["-F", missing_name, present_name])?;
+    let start = format!("==> {present_name} <==\none\n");
+    follower.wait_for(&start);
+    fs::write(&missing, "two\n")?;
+    follower.wait_for(&format!("{start}\n==> {missing_name} <==\ntwo\n"));
+    let (_, stderr) = follower.stop()?;
+
+    assert!(stderr.contains("cannot open"));
+    assert!(stderr.contains("has appeared"));
+    Ok(())
+}
+
+#[test]
+fn follow_descriptor_ignores_rotation() -> Result<()> {
+    let dir = TempDir::new()?;
+    let log = dir.path().join("app.log");
+    fs::write(&log, "one\n")?;
+
+    let follower = Follower::spawn(&["-f", log.to_str().unwrap()])?;
+    follower.wait_for("one\n");
+    fs::rename(&log, dir.path().join("app.log.1"))?;
+    fs::write(&log, "three\n")?;
+    append(&dir.path().join("app.log.1"), "two\n")?;
+    follower.wait_for("one\ntwo\n");
+    // The new file is never read, however many more polls go by
+    thread::sleep(Duration::from_millis(300));
+    let (stdout, _) = follower.stop()?;
+
+    assert_eq!(stdout, "one\ntwo\n");
+    Ok(())
+}
+
+#[test]
+fn follow_multiple_files() -> Result<()> {
+    let dir = TempDir::new()?;
+    let first = dir.path().join("first.log");
+    let second = dir.path().join("second.log");
+    fs::write(&first, "a\n")?;
+    fs::write(&second, "b\n")?;
+    let (first_name, second_name) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+    let follower = Follower::spawn(&["-f", first_name, second_name])?;
+    let start = format!("==> {first_name} <==\na\n\n==> {second_name} <==\nb\n");
+    follower.wait_for(&start);
+    append(&first, "c\n")?;
+    follower.wait_for(&format!("{start}\n==> {first_name} <==\nc\n"));
+    append(&first, "d\n")?;
+    follower.wait_for(&format!("{start}\n==> {first_name} <==\nc\nd\n"));
+    follower.stop()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_twelve_n3() -> Result<()> {
+    run_stdin(
+        &["-n", "3", "-"],
+        TWELVE,
+        "tests/expected/twelve.txt.n3.out",
+    )
+}
+
+#[cfg(unix)]
+#[test]
+fn dev_stdin_twelve_n3() -> Result<()> {
+    // A pipe can't be read from the end, so it is streamed like "-"
+    run_stdin(
+        &["-n", "3", "/dev/stdin"],
+        TWELVE,
+        "tests/expected/twelve.txt.n3.out",
+    )
+}
+
+#[cfg(unix)]
+#[test]
+fn dev_stdin_twelve_c12() -> Result<()> {
+    run_stdin(
+        &["-c", "12", "/dev/stdin"],
+        TWELVE,
+        "tests/expected/twelve.txt.c12.out",
+    )
+}
+
+#[test]
+fn stdin_twelve_n_plus_2() -> Result<()> {
+    run_stdin(&["-n", "+2"], TWELVE, "tests/expected/twelve.txt.n+2.out")
+}
+
+#[test]
+fn stdin_twelve_n200() -> Result<()> {
+    run_stdin(&["-n", "200"], TWELVE, "tests/expected/twelve.txt.n200.out")
+}
+
+#[test]
+fn stdin_three_n1() -> Result<()> {
+    run_stdin(&["-n", "1"], THREE, "tests/expected/three.txt.n1.out")
+}
+
+#[test]
+fn stdin_twelve_c12() -> Result<()> {
+    run_stdin(&["-c", "12"], TWELVE, "tests/expected/twelve.txt.c12.out")
+}
+
+#[test]
+fn stdin_twelve_c_plus_2() -> Result<()> {
+    run_stdin(&["-c", "+2"], TWELVE, "tests/expected/twelve.txt.c+2.out")
+}
+
+#[test]
+fn stdin_one_c8() -> Result<()> {
+    run_stdin(&["-c", "8"], ONE, "tests/expected/one.txt.c8.out")
+}
@@ -1,5 +1,16 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
 use clap::Parser;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{BuildHasher, RandomState};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write};
+
+/// Temporary files a `--global` run spreads its lines over once it outgrows
+/// `--max-memory`.
+const BUCKETS: usize = 16;
+
+/// Rough per-line cost of the `--global` hash map beyond the line and key.
+const ENTRY_OVERHEAD: u64 = 96;
 
 #[derive(Parser, Debug)]
 #[command(author, about)]
@@ -10,40 +21,346 @@ struct Args {
     out_file: String,
     #[arg(short('c'), long("count"))]
     count: bool,
+    #[arg(short('d'), long("repeated"), help = "Only print duplicated lines")]
+    repeated: bool,
+    #[arg(short('u'), long("unique"), help = "Only print unique lines")]
+    unique: bool,
+    #[arg(short('i'), long("ignore-case"), help = "Ignore case when comparing")]
+    ignore_case: bool,
+    #[arg(
+        short('f'),
+        long("skip-fields"),
+        value_name = "N",
+        default_value_t = 0,
+        help = "Skip the first N fields when comparing"
+    )]
+    skip_fields: usize,
+    #[arg(
+        short('s'),
+        long("skip-chars"),
+        value_name = "N",
+        default_value_t = 0,
+        help = "Skip the first N characters when comparing"
+    )]
+    skip_chars: usize,
+    #[arg(
+        short('w'),
+        long("check-chars"),
+        value_name = "N",
+        help = "Compare no more than N characters"
+    )]
+    check_chars: Option<usize>,
+    #[arg(
+        long("global"),
+        help = "Remove duplicates anywhere in the input, not just adjacent ones"
+    )]
+    global: bool,
+    #[arg(
+        long("max-memory"),
+        value_name = "SIZE",
+        value_parser = parse_memory,
+        requires = "global",
+        help = "Spill --global lines to disk past SIZE bytes [K, M or G]"
+    )]
+    max_memory: Option<u64>,
+}
+
+fn parse_memory(s: &str) -> Result<u64> {
+    let (digits, unit) = match s.strip_suffix(['K', 'M', 'G']) {
+        Some(digits) => (digits, &s[digits.len()..]),
+        None => (s, ""),
+    };
+    let Ok(size) = digits.parse::<u64>() else {
+        bail!("invalid size \"{s}\"");
+    };
+    let shift = match unit {
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        _ => 0,
+    };
+    size.checked_mul(1 << shift)
+        .ok_or_else(|| anyhow::anyhow!("size \"{s}\" is too large"))
+}
+
+/// The part of `line` compared against its neighbours: the line without its
+/// newline, less the skipped fields and characters, cut to `--check-chars`.
+/// Fields are runs of blanks followed by non-blanks, and characters are
+/// counted in bytes, as in GNU uniq.
+fn key<'a>(line: &'a [u8], args: &Args) -> &'a [u8] {
+    let is_blank = |b: &u8| *b == b' ' || *b == b'\t';
+    let mut key = line.strip_suffix(b"\n").unwrap_or(line);
+    for _ in 0..args.skip_fields {
+        let start = key.iter().position(|b| !is_blank(b)).unwrap_or(key.len());
+        key = &key[start..];
+        let end = key.iter().position(is_blank).unwrap_or(key.len());
+        key = &key[end..];
+    }
+    key = &key[args.skip_chars.min(key.len())..];
+    if let Some(n) = args.check_chars {
+        key = &key[..n.min(key.len())];
+    }
+    key
+}
+
+fn same(old_line: &[u8], new_line: &[u8], args: &Args) -> bool {
+    let (old_key, new_key) = (key(old_line, args), key(new_line, args));
+    if args.ignore_case {
+        old_key.eq_ignore_ascii_case(new_key)
+    } else {
+        old_key == new_key
+    }
+}
+
+/// A distinct line in `--global` mode: the first line seen with its key,
+/// where it was seen and how many lines share the key.
+#[derive(Debug)]
+struct Entry {
+    seq: u64,
+    count: u64,
+    printed: bool,
+    line: Vec<u8>,
+}
+
+type Entries = Box<dyn Iterator<Item = Result<Entry>>>;
+
+impl Entry {
+    /// Sorts by first occurrence, or by falling frequency with ties in order
+    /// of first occurrence.
+    fn rank(&self, by_count: bool) -> (u64, u64) {
+        let count = if by_count { u64::MAX - self.count } else { 0 };
+        (count, self.seq)
+    }
+
+    fn merge(&mut self, other: Entry) {
+        self.count += other.count;
+        self.printed |= other.printed;
+        if other.seq < self.seq {
+            self.seq = other.seq;
+            self.line = other.line;
+        }
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.seq.to_le_bytes())?;
+        out.write_all(&self.count.to_le_bytes())?;
+        out.write_all(&[self.printed as u8])?;
+        out.write_all(&(self.line.len() as u64).to_le_bytes())?;
+        out.write_all(&self.line)
+    }
+
+    fn read(input: &mut impl Read) -> io::Result<Option<Entry>> {
+        let mut word = [0; 8];
+        match input.read_exact(&mut word) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let seq = u64::from_le_bytes(word);
+        input.read_exact(&mut word)?;
+        let count = u64::from_le_bytes(word);
+        let mut printed = [0];
+        input.read_exact(&mut printed)?;
+        input.read_exact(&mut word)?;
+        let mut line = vec![0; u64::from_le_bytes(word) as usize];
+        input.read_exact(&mut line)?;
+        Ok(Some(Entry {
+            seq,
+            count,
+            printed: printed[0] != 0,
+            line,
+        }))
+    }
+}
+
+/// Reads back the entries written to a temporary file.
+fn read_entries(mut file: File) -> Result<Entries> {
+    file.rewind()?;
+    let mut file = BufReader::new(file);
+    Ok(Box::new(std::iter::from_fn(move || {
+        Entry::read(&mut file).transpose().map(|entry| Ok(entry?))
+    })))
+}
+
+/// Merges runs of entries that are each in rank order into one.
+fn merge_runs(mut runs: Vec<Entries>, by_count: bool) -> Result<Entries> {
+    let mut heads = runs
+        .iter_mut()
+        .map(|run| run.next().transpose())
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Box::new(std::iter::from_fn(move || {
+        let (i, _) = heads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, head)| Some((i, head.as_ref()?.rank(by_count))))
+            .min_by_key(|&(_, rank)| rank)?;
+        match runs[i].next().transpose() {
+            Ok(next) => std::mem::replace(&mut heads[i], next).map(Ok),
+            Err(e) => Some(Err(e)),
+        }
+    })))
+}
+
+/// Removes duplicate lines across the whole input for `--global`.
+struct Global<'a> {
+    args: &'a Args,
+    max_memory: u64,
+    by_count: bool,
+}
+
+impl Global<'_> {
+    fn key(&self, line: &[u8]) -> Vec<u8> {
+        let key = key(line, self.args);
+        if self.args.ignore_case {
+            key.to_ascii_lowercase()
+        } else {
+            key.to_vec()
+        }
+    }
+
+    /// Merges the entries sharing a key and returns them in rank order,
+    /// calling `on_new` on each key's first entry while they all fit in
+    /// memory. Beyond `--max-memory` the entries seen so far and the rest of
+    /// the input are spilled to disk.
+    fn dedup(
+        &self,
+        mut entries: impl Iterator<Item = Result<Entry>>,
+        mut on_new: impl FnMut(&mut Entry) -> Result<()>,
+    ) -> Result<Entries> {
+        let mut seen: HashMap<Vec<u8>, Entry> = HashMap::new();
+        let mut used = 0;
+        while let Some(entry) = entries.next() {
+            let mut entry = entry?;
+            let key = self.key(&entry.line);
+            match seen.get_mut(&key) {
+                Some(first) => first.merge(entry),
+                None => {
+                    on_new(&mut entry)?;
+                    used += (key.len() + entry.line.len()) as u64 + ENTRY_OVERHEAD;
+                    seen.insert(key, entry);
+                    if used > self.max_memory && seen.len() > 1 {
+                        return self.spill(seen.into_values().map(Ok).chain(entries));
+                    }
+                }
+            }
+        }
+        let mut entries: Vec<Entry> = seen.into_values().collect();
+        entries.sort_by_key(|entry| entry.rank(self.by_count));
+        Ok(Box::new(entries.into_iter().map(Ok)))
+    }
+
+    /// Spreads the entries over temporary files by a hash of their key, so
+    /// every key ends up in a single file, then dedups each file in turn.
+    fn spill(&self, entries: impl Iterator<Item = Result<Entry>>) -> Result<Entries> {
+        let hasher = RandomState::new();
+        let mut buckets = Vec::with_capacity(BUCKETS);
+        for _ in 0..BUCKETS {
+            buckets.push((BufWriter::new(tempfile::tempfile()?), false));
+        }
+        for entry in entries {
+            let entry = entry?;
+            let (bucket, used) =
+                &mut buckets[hasher.hash_one(self.key(&entry.line)) as usize % BUCKETS];
+            entry.write(bucket)?;
+            *used = true;
+        }
+
+        let mut runs = Vec::new();
+        for (bucket, used) in buckets {
+            if !used {
+                continue;
+            }
+            let mut run = BufWriter::new(tempfile::tempfile()?);
+            for entry in self.dedup(read_entries(bucket.into_inner()?)?, |_| Ok(()))? {
+                entry?.write(&mut run)?;
+            }
+            runs.push(read_entries(run.into_inner()?)?);
+        }
+        merge_runs(runs, self.by_count)
+    }
+}
+
+/// Prints each distinct line once, in order of first occurrence or, with
+/// `--count`, by falling frequency. Lines are printed as they're first seen
+/// when nothing depends on the counts. Unlike the adjacent mode, every line
+/// printed ends with a newline.
+fn run_global(args: &Args, file: impl BufRead, out_file: &mut dyn Write) -> Result<()> {
+    let global = Global {
+        args,
+        max_memory: args.max_memory.unwrap_or(u64::MAX),
+        by_count: args.count,
+    };
+    let streaming = !args.count && !args.repeated && !args.unique;
+    let print = |line: &[u8], out_file: &mut dyn Write| -> Result<()> {
+        out_file.write_all(line)?;
+        if !line.ends_with(b"\n") {
+            out_file.write_all(b"\n")?;
+        }
+        Ok(())
+    };
+
+    let lines = clir::lines(file).zip(0..).map(|(line, seq)| {
+        Ok(Entry {
+            seq,
+            count: 1,
+            printed: false,
+            line: line?,
+        })
+    });
+    let entries = global.dedup(lines, |entry| {
+        if streaming {
+            print(&entry.line, out_file)?;
+            entry.printed = true;
+        }
+        Ok(())
+    })?;
+    for entry in entries {
+        let entry = entry?;
+        if entry.printed || (args.repeated && entry.count == 1) || (args.unique && entry.count > 1)
+        {
+            continue;
+        }
+        if args.count {
+            write!(out_file, "{:>4} ", entry.count)?;
+        }
+        print(&entry.line, out_file)?;
+    }
+    Ok(())
 }
 
 fn run(args: Args) -> Result<()> {
-    let mut new_line = String::new();
-    let mut old_line = String::new();
+    let mut old_line: Vec<u8> = Vec::new();
     let mut count = 0;
-    let mut file = clir::open(&args.in_file)?;
+    let file = clir::open(&args.in_file)?;
     let mut out_file = clir::out(&args.out_file)?;
-    loop {
-        let read_bytes = file.read_line(&mut new_line)?;
-        if read_bytes == 0 {
-            break;
+    if args.global {
+        return run_global(&args, file, &mut out_file);
+    }
+
+    let mut print = |count: usize, line: &[u8]| -> Result<()> {
+        if (args.repeated && count == 1) || (args.unique && count > 1) {
+            return Ok(());
+        }
+        if args.count {
+            write!(out_file, "{count:>4} ")?;
         }
-        if old_line.trim_end() != new_line.trim_end() {
+        out_file.write_all(line)?;
+        Ok(())
+    };
+
+    for new_line in clir::lines(file) {
+        let new_line = new_line?;
+        if count == 0 || !same(&old_line, &new_line, &args) {
             if count > 0 {
-                if args.count {
-                    write!(out_file, "{count:>4} {old_line}")?;
-                } else {
-                    write!(out_file, "{old_line}")?;
-                }
+                print(count, &old_line)?;
             }
-            old_line = new_line.clone();
+            old_line = new_line;
             count = 0;
         }
         count += 1;
-        new_line.clear();
     }
 
-    if old_line != "" {
-        if args.count {
-            write!(out_file, "{count:>4} {old_line}")?;
-        } else {
-            write!(out_file, "{old_line}")?;
-        }
+    if count > 0 {
+        print(count, &old_line)?;
     }
     Ok(())
 }
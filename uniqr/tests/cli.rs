@@ -80,6 +80,9 @@ const T6: Test = Test {
     out_count: "tests/expected/t6.txt.c.out",
 };
 
+const FIELDS: &str = "tests/inputs/fields.txt";
+const UNSORTED: &str = "tests/inputs/unsorted.txt";
+
 // --------------------------------------------------
 fn gen_bad_file() -> String {
     loop {
@@ -222,6 +225,17 @@ fn run_stdin_outfile_count(test: &Test) -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_flags(args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read_to_string(expected_file)?;
+    let output = Command::cargo_bin(PRG)?.args(args).output().expect("fail");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty() -> Result<()> {
@@ -617,3 +631,132 @@ fn t6_outfile_count() -> Result<()> {
 fn t6_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&T6)
 }
+
+// --------------------------------------------------
+#[test]
+fn repeated() -> Result<()> {
+    run_flags(&[FIELDS, "-d"], "tests/expected/fields.txt.d.out")
+}
+
+#[test]
+fn unique() -> Result<()> {
+    run_flags(&[FIELDS, "--unique"], "tests/expected/fields.txt.u.out")
+}
+
+#[test]
+fn repeated_and_unique() -> Result<()> {
+    run_flags(&[FIELDS, "-d", "-u"], "tests/inputs/empty.txt")
+}
+
+#[test]
+fn ignore_case_count() -> Result<()> {
+    run_flags(&[FIELDS, "-i", "-c"], "tests/expected/fields.txt.i.c.out")
+}
+
+#[test]
+fn skip_fields() -> Result<()> {
+    run_flags(&[FIELDS, "-f", "1"], "tests/expected/fields.txt.f1.out")
+}
+
+#[test]
+fn skip_fields_ignore_case_repeated() -> Result<()> {
+    run_flags(
+        &[FIELDS, "--skip-fields", "1", "--ignore-case", "--repeated"],
+        "tests/expected/fields.txt.f1.i.d.out",
+    )
+}
+
+#[test]
+fn skip_chars_check_chars() -> Result<()> {
+    run_flags(
+        &[FIELDS, "-s", "2", "--check-chars", "5"],
+        "tests/expected/fields.txt.s2.w5.out",
+    )
+}
+
+#[test]
+fn dies_bad_skip_fields() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "x", FIELDS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'x'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn global() -> Result<()> {
+    run_flags(
+        &[UNSORTED, "--global"],
+        "tests/expected/unsorted.txt.global.out",
+    )
+}
+
+#[test]
+fn global_count() -> Result<()> {
+    run_flags(
+        &[UNSORTED, "--global", "-c"],
+        "tests/expected/unsorted.txt.global.c.out",
+    )
+}
+
+#[test]
+fn global_ignore_case_count() -> Result<()> {
+    run_flags(
+        &[UNSORTED, "--global", "-i", "-c"],
+        "tests/expected/unsorted.txt.global.i.c.out",
+    )
+}
+
+#[test]
+fn global_repeated() -> Result<()> {
+    run_flags(
+        &[UNSORTED, "--global", "-d"],
+        "tests/expected/unsorted.txt.global.d.out",
+    )
+}
+
+#[test]
+fn global_spills_past_max_memory() -> Result<()> {
+    // Every multiple of 3 appears twice, so it sorts ahead of the rest
+    let input: String = (0..1000)
+        .chain((0..1000).step_by(3))
+        .map(|i| format!("{i}\n"))
+        .collect();
+    let (twice, once): (Vec<u32>, Vec<u32>) = (0..1000).partition(|i| i % 3 == 0);
+    let expected: String = twice
+        .iter()
+        .map(|i| format!("   2 {i}\n"))
+        .chain(once.iter().map(|i| format!("   1 {i}\n")))
+        .collect();
+    for max_memory in ["1", "4K", "1G"] {
+        Command::cargo_bin(PRG)?
+            .args(["--global", "-c", "--max-memory", max_memory])
+            .write_stdin(input.clone())
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+    Ok(())
+}
+
+#[test]
+fn dies_max_memory_without_global() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--max-memory", "1M", UNSORTED])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--global"));
+    Ok(())
+}
+
+#[test]
+fn dies_bad_max_memory() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--global", "--max-memory", "1X", UNSORTED])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid size \"1X\""));
+    Ok(())
+}
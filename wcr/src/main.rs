@@ -1,12 +1,25 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use clap::ArgAction;
 use clap::Parser;
+use clir::Report;
+use serde::Serialize;
+use std::io::{self, BufRead, Read};
+use std::ops::AddAssign;
+use std::sync::{Mutex, mpsc};
+use std::thread;
+use unicode_width::UnicodeWidthChar;
+
+/// Bytes read at a time and counted on whichever thread is free.
+const BLOCK_SIZE: usize = 1 << 20;
 
 #[derive(Parser, Debug)]
 #[command(about, version, author)]
 struct Args {
     #[arg(value_name = "FILE", default_value = "-")]
     files: Vec<String>,
+    /// Read NUL-separated filenames from F (or stdin for `-`)
+    #[arg(long, value_name = "F", conflicts_with("files"))]
+    files0_from: Option<String>,
     #[arg(short, long, action=ArgAction::SetTrue)]
     lines: bool,
     #[arg(short, long, action=ArgAction::SetTrue)]
@@ -15,20 +28,190 @@ struct Args {
     bytes: bool,
     #[arg(short('m'), long, conflicts_with("bytes"))]
     chars: bool,
+    /// Print the display width of the longest line
+    #[arg(short('L'), long, action=ArgAction::SetTrue)]
+    max_line_length: bool,
+    /// Print the counts as one JSON document
+    #[arg(long, conflicts_with("ndjson"))]
+    json: bool,
+    /// Print the counts as a JSON object per line
+    #[arg(long)]
+    ndjson: bool,
+}
+
+/// The display width of a stretch of a line. A tab makes it depend on the
+/// column it starts at, so this keeps the `lead` columns before the first
+/// tab and the columns `past_tab` the stop the last tab reaches.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Width {
+    lead: usize,
+    past_tab: Option<usize>,
+}
+
+impl Width {
+    const TAB: Width = Width {
+        lead: 0,
+        past_tab: Some(0),
+    };
+
+    fn then(self, next: Width) -> Width {
+        match (self.past_tab, next.past_tab) {
+            (None, _) => Width {
+                lead: self.lead + next.lead,
+                past_tab: next.past_tab,
+            },
+            (Some(past), None) => Width {
+                lead: self.lead,
+                past_tab: Some(past + next.lead),
+            },
+            (Some(past), Some(more)) => Width {
+                lead: self.lead,
+                past_tab: Some(tab_stop(past + next.lead) + more),
+            },
+        }
+    }
+
+    fn widen(&mut self, columns: usize) {
+        match &mut self.past_tab {
+            Some(past) => *past += columns,
+            None => self.lead += columns,
+        }
+    }
+
+    /// The column this ends at when started at `column`.
+    fn end(self, column: usize) -> usize {
+        match self.past_tab {
+            Some(past) => tab_stop(column + self.lead) + past,
+            None => column + self.lead,
+        }
+    }
+}
+
+/// The tab stop a tab at `column` moves to.
+fn tab_stop(column: usize) -> usize {
+    (column / 8 + 1) * 8
 }
 
-fn pv(args: &Args, lines: usize, words: usize, bytes: usize, chars: usize, filename: &str) {
+/// The counts for a file, or a block of one. `lines` counts newlines and
+/// `max_line` the widest line between two of them until [`Counts::finish`]
+/// adds any unterminated last line and the first and last lines' widths.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Counts {
+    lines: usize,
+    words: usize,
+    bytes: usize,
+    chars: usize,
+    starts_in_word: bool,
+    ends_in_word: bool,
+    ends_in_newline: bool,
+    max_line: usize,
+    head: Width,
+    tail: Width,
+}
+
+impl Counts {
+    /// Counts following block `next`, joining a word split between them.
+    fn then(self, next: Counts) -> Counts {
+        if self.bytes == 0 {
+            return next;
+        }
+        if next.bytes == 0 {
+            return self;
+        }
+        let (head, tail, max_line) = match (self.lines > 0, next.lines > 0) {
+            (false, false) => {
+                let line = self.head.then(next.head);
+                (line, line, 0)
+            }
+            (false, true) => (self.head.then(next.head), next.tail, next.max_line),
+            (true, false) => (self.head, self.tail.then(next.head), self.max_line),
+            (true, true) => (
+                self.head,
+                next.tail,
+                self.max_line
+                    .max(next.max_line)
+                    .max(self.tail.then(next.head).end(0)),
+            ),
+        };
+        Counts {
+            lines: self.lines + next.lines,
+            words: self.words + next.words - (self.ends_in_word && next.starts_in_word) as usize,
+            bytes: self.bytes + next.bytes,
+            chars: self.chars + next.chars,
+            starts_in_word: self.starts_in_word,
+            ends_in_word: next.ends_in_word,
+            ends_in_newline: next.ends_in_newline,
+            max_line,
+            head,
+            tail,
+        }
+    }
+
+    fn finish(mut self) -> Counts {
+        if self.bytes > 0 && !self.ends_in_newline {
+            self.lines += 1;
+        }
+        self.max_line = self.max_line.max(self.head.end(0)).max(self.tail.end(0));
+        self
+    }
+}
+
+impl AddAssign for Counts {
+    fn add_assign(&mut self, other: Counts) {
+        self.lines += other.lines;
+        self.words += other.words;
+        self.bytes += other.bytes;
+        self.chars += other.chars;
+        self.max_line = self.max_line.max(other.max_line);
+    }
+}
+
+/// The selected counts for a file, or for all of them when `file` is `None`,
+/// as reported by `--json` and `--ndjson`.
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    words: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chars: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_line_length: Option<usize>,
+}
+
+impl<'a> Record<'a> {
+    fn new(args: &Args, counts: &Counts, file: Option<&'a str>) -> Self {
+        Record {
+            file,
+            lines: args.lines.then_some(counts.lines),
+            words: args.words.then_some(counts.words),
+            bytes: args.bytes.then_some(counts.bytes),
+            chars: args.chars.then_some(counts.chars),
+            max_line_length: args.max_line_length.then_some(counts.max_line),
+        }
+    }
+}
+
+fn pv(args: &Args, counts: &Counts, filename: &str) {
     if args.lines {
-        print!("{lines:>8}")
+        print!("{:>8}", counts.lines)
     }
     if args.words {
-        print!("{words:>8}")
+        print!("{:>8}", counts.words)
     }
     if args.bytes {
-        print!("{bytes:>8}")
+        print!("{:>8}", counts.bytes)
     }
     if args.chars {
-        print!("{chars:>8}")
+        print!("{:>8}", counts.chars)
+    }
+    if args.max_line_length {
+        print!("{:>8}", counts.max_line)
     }
     if filename != "-" {
         print!(" {filename}")
@@ -37,65 +220,289 @@ fn pv(args: &Args, lines: usize, words: usize, bytes: usize, chars: usize, filen
     println!()
 }
 
+/// Whether an ASCII byte is whitespace by `char::is_whitespace`.
+fn is_ascii_space(b: u8) -> bool {
+    matches!(b, b'\t'..=b'\r' | b' ')
+}
+
+/// Counts the words in `bytes`, where runs of invalid UTF-8 count as word
+/// characters, and notes whether it starts and ends inside a word.
+fn count_words_in(bytes: &[u8]) -> (usize, bool, bool) {
+    let mut words = 0;
+    let mut in_word = false;
+    let mut starts_in_word = None;
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid();
+        if valid.is_ascii() {
+            // Branch-free so the compiler can vectorise the common case
+            for &b in valid.as_bytes() {
+                let space = is_ascii_space(b);
+                words += (!in_word && !space) as usize;
+                in_word = !space;
+            }
+            if let Some(&b) = valid.as_bytes().first() {
+                starts_in_word.get_or_insert(!is_ascii_space(b));
+            }
+        } else {
+            for c in valid.chars() {
+                let space = c.is_whitespace();
+                starts_in_word.get_or_insert(!space);
+                if space {
+                    in_word = false;
+                } else if !in_word {
+                    words += 1;
+                    in_word = true;
+                }
+            }
+        }
+        if !chunk.invalid().is_empty() {
+            starts_in_word.get_or_insert(true);
+            if !in_word {
+                words += 1;
+                in_word = true;
+            }
+        }
+    }
+    (words, starts_in_word.unwrap_or(false), in_word)
+}
+
+#[cfg(test)]
+fn count_words(bytes: &[u8]) -> usize {
+    count_words_in(bytes).0
+}
+
+fn count_chars(bytes: &[u8]) -> usize {
+    bytes
+        .utf8_chunks()
+        .map(|chunk| {
+            let valid = chunk.valid().as_bytes();
+            // Every byte but a continuation byte starts a character
+            valid.iter().filter(|&&b| (b & 0xc0) != 0x80).count()
+        })
+        .sum()
+}
+
+/// Measures the lines in `bytes`, where invalid UTF-8 takes no columns,
+/// returning the first line, the widest between two newlines and the last.
+fn line_widths(bytes: &[u8]) -> (Width, usize, Width) {
+    let mut head = None;
+    let mut max_line = 0;
+    let mut line = Width::default();
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\n' => {
+                    if head.is_none() {
+                        head = Some(line);
+                    } else {
+                        max_line = max_line.max(line.end(0));
+                    }
+                    line = Width::default();
+                }
+                '\t' => line = line.then(Width::TAB),
+                c => line.widen(c.width().unwrap_or(0)),
+            }
+        }
+    }
+    (head.unwrap_or(line), max_line, line)
+}
+
+fn count_block(block: &[u8], widths: bool) -> Counts {
+    let (words, starts_in_word, ends_in_word) = count_words_in(block);
+    let (head, max_line, tail) = if widths {
+        line_widths(block)
+    } else {
+        Default::default()
+    };
+    Counts {
+        lines: memchr::memchr_iter(b'\n', block).count(),
+        words,
+        bytes: block.len(),
+        chars: count_chars(block),
+        starts_in_word,
+        ends_in_word,
+        ends_in_newline: block.last() == Some(&b'\n'),
+        max_line,
+        head,
+        tail,
+    }
+}
+
+/// Reads up to `block_size` bytes after those carried over from the last
+/// block, carrying any character cut off at the end on to the next. Returns
+/// `None` at the end of the input.
+fn read_block(
+    file: &mut impl Read,
+    carry: &mut Vec<u8>,
+    block_size: usize,
+) -> Result<Option<Vec<u8>>> {
+    let mut block = std::mem::take(carry);
+    let wanted = block_size.saturating_sub(block.len()).max(1);
+    let read = file.take(wanted as u64).read_to_end(&mut block)?;
+    if block.is_empty() {
+        return Ok(None);
+    }
+    if read == wanted {
+        // A character cut off here starts in the last three bytes, at the
+        // last byte there that isn't a continuation byte
+        let tail = block.len().saturating_sub(3);
+        if let Some(start) = block[tail..].iter().rposition(|&b| (b & 0xc0) != 0x80) {
+            let start = tail + start;
+            let len = match block[start] {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            if start + len > block.len() {
+                if start > 0 {
+                    *carry = block.split_off(start);
+                } else {
+                    // Nothing else in the block, so finish the character
+                    let rest = start + len - block.len();
+                    file.take(rest as u64).read_to_end(&mut block)?;
+                }
+            }
+        }
+    }
+    Ok(Some(block))
+}
+
+/// Runs `handle` on each message from `rx` until it closes. The lock on `rx`
+/// is released before `handle` runs, so other workers can take the next one.
+fn work<T>(rx: &Mutex<mpsc::Receiver<T>>, mut handle: impl FnMut(T)) {
+    loop {
+        let message = rx.lock().unwrap().recv();
+        let Ok(message) = message else { break };
+        handle(message);
+    }
+}
+
+/// Counts `file` a block at a time, spreading the blocks over a pool of
+/// threads once there's more than one. Line widths are only measured when
+/// `widths` is set.
+fn count(mut file: impl Read, block_size: usize, widths: bool) -> Result<Counts> {
+    let mut carry = Vec::new();
+    let Some(first) = read_block(&mut file, &mut carry, block_size)? else {
+        return Ok(Counts::default());
+    };
+    let Some(second) = read_block(&mut file, &mut carry, block_size)? else {
+        return Ok(count_block(&first, widths).finish());
+    };
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let (tx, rx) = mpsc::sync_channel::<(usize, Vec<u8>)>(threads);
+    let rx = Mutex::new(rx);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut counted = Vec::new();
+                    work(&rx, |(idx, block)| {
+                        counted.push((idx, count_block(&block, widths)));
+                    });
+                    counted
+                })
+            })
+            .collect();
+
+        let read = || -> Result<()> {
+            tx.send((0, first)).ok();
+            tx.send((1, second)).ok();
+            let mut idx = 2;
+            while let Some(block) = read_block(&mut file, &mut carry, block_size)? {
+                tx.send((idx, block)).ok();
+                idx += 1;
+            }
+            Ok(())
+        };
+        let read = read();
+        drop(tx);
+
+        let mut counted: Vec<(usize, Counts)> = workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect();
+        read?;
+        counted.sort_by_key(|(idx, _)| *idx);
+        Ok(counted
+            .into_iter()
+            .fold(Counts::default(), |total, (_, counts)| total.then(counts))
+            .finish())
+    })
+}
+
+/// Reads the NUL-separated filenames listed in `filename`.
+fn files0(filename: &str) -> Result<Vec<String>> {
+    let list = clir::open(filename).map_err(|err| anyhow!("{filename}: {err}"))?;
+    let mut files = Vec::new();
+    for name in list.split(b'\0') {
+        match String::from_utf8(name?) {
+            Ok(name) if name.is_empty() => {
+                eprintln!("{filename}: invalid zero-length file name");
+            }
+            Ok(name) => files.push(name),
+            Err(err) => eprintln!(
+                "{filename}: {}: file name is not valid UTF-8",
+                String::from_utf8_lossy(err.as_bytes())
+            ),
+        }
+    }
+    Ok(files)
+}
+
 fn run(mut args: Args) -> Result<()> {
-    if [args.words, args.lines, args.bytes, args.chars]
-        .iter()
-        .all(|flag| !flag)
+    if [
+        args.words,
+        args.lines,
+        args.bytes,
+        args.chars,
+        args.max_line_length,
+    ]
+    .iter()
+    .all(|flag| !flag)
     {
         args.lines = true;
         args.words = true;
         args.bytes = true;
     }
 
-    let mut total_bytes_read = 0;
-    let mut total_lines_read = 0;
-    let mut total_words_read = 0;
-    let mut total_chars_read = 0;
-    let file_count = args.files.len();
-
-    for filename in &args.files {
-        let mut file = clir::open(&filename)?;
-        let mut bytes_read_total: usize = 0;
-        let mut lines_read_total: usize = 0;
-        let mut chars_read_total: usize = 0;
-        let mut words_read_total: usize = 0;
-        let mut line = String::new();
-        loop {
-            let bytes_read = file.read_line(&mut line)?;
-            if bytes_read == 0 {
-                break;
+    let files = match &args.files0_from {
+        Some(list) => files0(list)?,
+        None => args.files.clone(),
+    };
+
+    let mut report = if args.json {
+        Some(Report::json())
+    } else if args.ndjson {
+        Some(Report::ndjson())
+    } else {
+        None
+    };
+    let mut stdout = io::stdout();
+
+    let mut total = Counts::default();
+    for filename in &files {
+        let file = match clir::open(filename) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("{filename}: {err}");
+                continue;
+            }
+        };
+        let counts = count(file, BLOCK_SIZE, args.max_line_length)?;
+        match &mut report {
+            Some(report) => {
+                report.record(&mut stdout, &Record::new(&args, &counts, Some(filename)))?
             }
-            let words = line.split_whitespace();
-            words_read_total += words.count();
-            bytes_read_total += bytes_read;
-            lines_read_total += 1;
-            chars_read_total += line.chars().count();
-            line.clear();
-        }
-
-        pv(
-            &args,
-            lines_read_total,
-            words_read_total,
-            bytes_read_total,
-            chars_read_total,
-            &filename,
-        );
-
-        total_bytes_read += bytes_read_total;
-        total_lines_read += lines_read_total;
-        total_words_read += words_read_total;
-        total_chars_read += chars_read_total;
-    }
-    if file_count > 1 {
-        pv(
-            &args,
-            total_lines_read,
-            total_words_read,
-            total_bytes_read,
-            total_chars_read,
-            &"total",
-        );
+            None => pv(&args, &counts, filename),
+        }
+        total += counts;
+    }
+    if let Some(report) = &report {
+        report.finish(&mut stdout, &Record::new(&args, &total, None))?;
+    } else if files.len() > 1 {
+        pv(&args, &total, "total");
     }
     Ok(())
 }
@@ -106,3 +513,123 @@ fn main() {
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{count, count_chars, count_words, work};
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Mutex, mpsc};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_count_words() {
+        assert_eq!(count_words(b""), 0);
+        assert_eq!(count_words(b"  \n"), 0);
+        assert_eq!(count_words(b"I don't want the world.\n"), 5);
+        assert_eq!(count_words("caf\u{e9}\u{a0}au lait".as_bytes()), 3);
+        assert_eq!(count_words(b"foo\xffbar baz"), 2);
+        assert_eq!(count_words(b"\xff \xfe"), 2);
+    }
+
+    #[test]
+    fn test_count_chars() {
+        assert_eq!(count_chars(b""), 0);
+        assert_eq!(count_chars("caf\u{e9}\n".as_bytes()), 5);
+        assert_eq!(count_chars(b"ab\xffc"), 3);
+    }
+
+    #[test]
+    fn test_work_in_parallel() {
+        // Each worker waits inside its handler for the other to start one,
+        // which only happens if the receiver isn't locked while handling
+        let (tx, rx) = mpsc::channel();
+        tx.send(()).unwrap();
+        tx.send(()).unwrap();
+        drop(tx);
+        let rx = Mutex::new(rx);
+        let started = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    work(&rx, |()| {
+                        started.fetch_add(1, Ordering::SeqCst);
+                        let deadline = Instant::now() + Duration::from_secs(5);
+                        while started.load(Ordering::SeqCst) < 2 {
+                            assert!(Instant::now() < deadline, "handlers never overlapped");
+                            thread::yield_now();
+                        }
+                    })
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_count_blocks() {
+        let text = "I don't\twant the world.\n  caf\u{e9}\u{a0}au lait \u{1f600}x\n\
+                    \u{3000}end\u{2028}\n\nno newline";
+        let inputs = [
+            text.as_bytes().to_vec(),
+            [
+                text.as_bytes(),
+                b"\xff\xe2\x82 \xf0\x9f\x98\n\x80\x80\x80\x80\x80x",
+            ]
+            .concat(),
+            b"\n\n".to_vec(),
+            b"word".to_vec(),
+        ];
+        for input in inputs {
+            let whole = count(&input[..], input.len() + 1, true).unwrap();
+            assert_eq!(whole.words, count_words(&input));
+            assert_eq!(whole.chars, count_chars(&input));
+            assert_eq!(whole.bytes, input.len());
+            for block_size in 1..=9 {
+                let blocks = count(&input[..], block_size, true).unwrap();
+                assert_eq!(
+                    (
+                        blocks.lines,
+                        blocks.words,
+                        blocks.bytes,
+                        blocks.chars,
+                        blocks.max_line
+                    ),
+                    (
+                        whole.lines,
+                        whole.words,
+                        whole.bytes,
+                        whole.chars,
+                        whole.max_line
+                    ),
+                    "block size {block_size}"
+                );
+            }
+        }
+        assert_eq!(count(&b"a\nb"[..], 2, false).unwrap().lines, 2);
+        assert_eq!(count(&b""[..], 2, false).unwrap().lines, 0);
+    }
+
+    #[test]
+    fn test_max_line_length() {
+        let max_line = |input: &str| {
+            count(input.as_bytes(), input.len() + 1, true)
+                .unwrap()
+                .max_line
+        };
+        assert_eq!(max_line(""), 0);
+        assert_eq!(max_line("ab\nabcd\nabc"), 4);
+        assert_eq!(max_line("\u{4f60}\u{597d}\n"), 4);
+        assert_eq!(max_line("e\u{301}\n"), 1);
+        assert_eq!(max_line("\t\n"), 8);
+        assert_eq!(max_line("abc\tx\tyz\n"), 18);
+        assert_eq!(max_line("abcdefgh\t\n"), 16);
+        for block_size in 1..=9 {
+            let input = "a\tb\u{4f60}cdefg\th\n\t\t\n\u{4f60}\u{597d}\tx";
+            assert_eq!(
+                count(input.as_bytes(), block_size, true).unwrap().max_line,
+                25
+            );
+        }
+    }
+}
@@ -9,6 +9,7 @@ const PRG: &str = "wcr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const FILES0: &str = "tests/inputs/files0.txt";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -217,3 +218,108 @@ fn test_all_words_lines() -> Result<()> {
 fn test_all_bytes_lines() -> Result<()> {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn fox_max_line_length() -> Result<()> {
+    run(&["-L", FOX], "tests/expected/fox.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_lines_max_line_length() -> Result<()> {
+    run(&["-lL", ATLAMAL], "tests/expected/atlamal.txt.lL.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_max_line_length() -> Result<()> {
+    run(&["-L", EMPTY, FOX, ATLAMAL], "tests/expected/all.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from() -> Result<()> {
+    run(&["--files0-from", FILES0], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_stdin() -> Result<()> {
+    let input = fs::read(FILES0)?;
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["--files0-from", "-"])
+        .write_stdin(input)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_skips_non_utf8_name() -> Result<()> {
+    let mut input = b"bad\xff\0".to_vec();
+    input.extend(fs::read(FILES0)?);
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", "-"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predicate::str::contains(
+            "bad\u{fffd}: file name is not valid UTF-8",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files0_from_and_files() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", FILES0, FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--files0-from <F>' cannot be used with '[FILE]...'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_json() -> Result<()> {
+    run(
+        &["--json", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_lines_max_line_length_ndjson() -> Result<()> {
+    run(
+        &["--ndjson", "-lL", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.lL.ndjson.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_json_and_ndjson() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--json", "--ndjson", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--json' cannot be used with '--ndjson'",
+        ));
+    Ok(())
+}